@@ -1,15 +1,100 @@
+use serde::de::DeserializeOwned;
+
+use crate::ecs::components::SpriteBlend;
+
 // Sprite data
 pub const PLAYER_SPRITE_PATH: &str = "assets/Bullethellplayer.png";
-pub const PLAYER_SPRITE_ID: usize = 0;
-
 pub const BOSS_SPRITE_PATH: &str = "assets/BullethellBoss.png";
-pub const BOSS_SPRITE_ID: usize = 1;
-
 pub const BULLET_SPRITE_PATH: &str = "assets/bullet.png";
-pub const BULLET_SPRITE_ID: usize = 2;
-
 pub const EXPLOSION_SPRITE_PATH: &str = "assets/explo.png";
-pub const EXPLOSION_SPRITE_ID: usize = 3;
+pub const POWER_PICKUP_SPRITE_PATH: &str = "assets/bombcontainer.png";
+pub const BULLET_RED_SPRITE_PATH: &str = "assets/BullethellBulletRed.png";
+pub const BULLET_CYAN_SPRITE_PATH: &str = "assets/BullethellBulletCyan.png";
+pub const BULLET_GREEN_SPRITE_PATH: &str = "assets/BullethellBulletGreen.png";
+pub const BULLET_PURPLE_SPRITE_PATH: &str = "assets/BullethellBulletPurple.png";
+pub const BULLET_YELLOW_SPRITE_PATH: &str = "assets/BullethellBulletYellow.png";
+pub const BULLET_SHEET_SPRITE_PATH: &str = "assets/bullet_sheet.png";
+pub const BOSS2_SPRITE_PATH: &str = "assets/BullethellBoss2.png";
+pub const BOSS3_SPRITE_PATH: &str = "assets/BullethellBoss3.png";
+pub const BACKGROUND_SPRITE_PATH: &str = "assets/bullethellbg.png";
+pub const STARS_SPRITE_PATH: &str = "assets/bullethellbgSTARS.png";
+pub const FONT_SPRITE_PATH: &str = "assets/fonts/hud.png";
+
+/// Every spritesheet, in sprite id order
+pub const SPRITE_PATHS: [&str; 16] = [
+    PLAYER_SPRITE_PATH,
+    BOSS_SPRITE_PATH,
+    BULLET_SPRITE_PATH,
+    EXPLOSION_SPRITE_PATH,
+    POWER_PICKUP_SPRITE_PATH,
+    BULLET_RED_SPRITE_PATH,
+    BULLET_CYAN_SPRITE_PATH,
+    BULLET_GREEN_SPRITE_PATH,
+    BULLET_PURPLE_SPRITE_PATH,
+    BULLET_YELLOW_SPRITE_PATH,
+    BULLET_SHEET_SPRITE_PATH,
+    BOSS2_SPRITE_PATH,
+    BOSS3_SPRITE_PATH,
+    BACKGROUND_SPRITE_PATH,
+    STARS_SPRITE_PATH,
+    FONT_SPRITE_PATH,
+];
+
+/// Id of the spritesheet loaded from `path`, for data files that name sprites
+pub fn sprite_id(path: &str) -> Option<usize> {
+    SPRITE_PATHS
+        .iter()
+        .position(|sprite_path| *sprite_path == path)
+}
+
+/// Width and height of the PNG image at `path`, read from its header without
+/// decoding it
+pub fn image_size(path: &str) -> Result<(u32, u32), String> {
+    let mut header = [0; 24];
+    let mut file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    std::io::Read::read_exact(&mut file, &mut header).map_err(|e| format!("{}: {}", path, e))?;
+    // The signature is followed by the IHDR chunk, which starts with the size
+    if &header[..8] != b"\x89PNG\r\n\x1a\n" || &header[12..16] != b"IHDR" {
+        return Err(format!("{}: not a PNG image", path));
+    }
+    let be_u32 = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Ok((be_u32(&header[16..20]), be_u32(&header[20..24])))
+}
+
+/// Reads the RON file at `path`, errors name the file
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    ron::de::from_reader(file).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Defaults of optional fields in data files
+pub fn one<T: From<u8>>() -> T {
+    T::from(1)
+}
+
+pub fn blend() -> SpriteBlend {
+    SpriteBlend::Blend
+}
+
+// Stage data
+pub const STAGE_PATH: &str = "assets/stages/stage1.ron";
+pub const BEHAVIORS_PATH: &str = "assets/behaviors.ron";
+pub const BOSSES_PATH: &str = "assets/bosses.ron";
+pub const PREFABS_PATH: &str = "assets/prefabs.ron";
+pub const BACKGROUND_PATH: &str = "assets/background.ron";
+
+// Text
+pub const FONT_PATH: &str = "assets/fonts/hud.ron";
+pub const HUD_PATH: &str = "assets/hud.ron";
+
+// Difficulty presets by name
+pub const DEFAULT_DIFFICULTY: &str = "normal";
+pub const DIFFICULTY_PATHS: [(&str, &str); 4] = [
+    ("easy", "assets/difficulty/easy.ron"),
+    ("normal", "assets/difficulty/normal.ron"),
+    ("hard", "assets/difficulty/hard.ron"),
+    ("lunatic", "assets/difficulty/lunatic.ron"),
+];
 
 // tweak data
 pub const BULLET_SIZE: i32 = 16;
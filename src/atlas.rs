@@ -0,0 +1,255 @@
+use sdl2::image::LoadSurface;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator};
+use sdl2::surface::Surface;
+
+/// Largest atlas page, supported by practically every renderer. Images that
+/// are bigger get a page of their own.
+const MAX_PAGE_SIZE: u32 = 2048;
+/// Empty pixels between images, so scaled sprites do not bleed into their
+/// neighbours
+const PADDING: u32 = 2;
+
+/// Where an image ended up in the atlas
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasRegion {
+    pub page: usize,
+    /// Position and size of the whole image on its page
+    pub rect: Rect,
+}
+
+/// Color, alpha and blend mode a page is drawn with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureStyle {
+    pub tint: Color,
+    pub alpha: u8,
+    pub blend: BlendMode,
+}
+
+impl Default for TextureStyle {
+    fn default() -> Self {
+        TextureStyle {
+            tint: Color::WHITE,
+            alpha: u8::MAX,
+            blend: BlendMode::Blend,
+        }
+    }
+}
+
+/// Every sprite packed into as few textures as fit, so drawing does not switch
+/// textures for every sprite
+pub struct Atlas<'a> {
+    pages: Vec<Texture<'a>>,
+    /// Style last set on each page, it is only changed when a draw needs a
+    /// different one so SDL can batch draws in between
+    styles: Vec<TextureStyle>,
+    /// Indexed by sprite id
+    regions: Vec<AtlasRegion>,
+}
+
+impl<'a> Atlas<'a> {
+    /// Loads the images at `paths` and packs them into atlas pages. Sprite ids
+    /// stay the index of their path.
+    pub fn build<T>(
+        paths: &[&str],
+        texture_creator: &'a TextureCreator<T>,
+    ) -> Result<Self, String> {
+        let mut images = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut image = Surface::from_file(path).map_err(|e| format!("{}: {}", path, e))?;
+            // Copy pixels as they are instead of blending them onto the page
+            image
+                .set_blend_mode(BlendMode::None)
+                .map_err(|e| format!("{}: {}", path, e))?;
+            images.push(image);
+        }
+
+        let sizes: Vec<_> = images.iter().map(|image| image.size()).collect();
+        let (regions, page_sizes) = pack(&sizes, MAX_PAGE_SIZE);
+
+        let mut page_surfaces = Vec::with_capacity(page_sizes.len());
+        for (width, height) in page_sizes {
+            page_surfaces.push(Surface::new(width, height, PixelFormatEnum::RGBA32)?);
+        }
+        for ((image, region), path) in images.iter().zip(regions.iter()).zip(paths) {
+            image
+                .blit(None, &mut page_surfaces[region.page], region.rect)
+                .map_err(|e| format!("{}: {}", path, e))?;
+        }
+
+        let mut pages = Vec::with_capacity(page_surfaces.len());
+        for surface in page_surfaces.iter() {
+            let mut page = texture_creator
+                .create_texture_from_surface(surface)
+                .map_err(|e| e.to_string())?;
+            page.set_blend_mode(BlendMode::Blend);
+            pages.push(page);
+        }
+
+        Ok(Atlas {
+            styles: vec![TextureStyle::default(); pages.len()],
+            pages,
+            regions,
+        })
+    }
+
+    pub fn region(&self, sprite: usize) -> AtlasRegion {
+        self.regions[sprite]
+    }
+
+    /// Page and rectangle on it of `src_rect` on the spritesheet `sprite`, cut
+    /// down to the part inside the image so neighbouring images are never
+    /// drawn. Also returns where that part is within `src_rect`, or `None` if
+    /// none of `src_rect` is inside the image.
+    pub fn locate(&self, sprite: usize, src_rect: Rect) -> Option<(usize, Rect, Rect)> {
+        let region = self.regions[sprite];
+        let rect = Rect::new(
+            region.rect.x() + src_rect.x(),
+            region.rect.y() + src_rect.y(),
+            src_rect.width(),
+            src_rect.height(),
+        );
+        let clipped = rect.intersection(region.rect)?;
+        let visible = Rect::new(
+            clipped.x() - rect.x(),
+            clipped.y() - rect.y(),
+            clipped.width(),
+            clipped.height(),
+        );
+        Some((region.page, clipped, visible))
+    }
+
+    /// The texture of `page`, ready to be drawn with `style`
+    pub fn texture(&mut self, page: usize, style: TextureStyle) -> &Texture<'a> {
+        let texture = &mut self.pages[page];
+        let current = &mut self.styles[page];
+        if current.tint != style.tint {
+            texture.set_color_mod(style.tint.r, style.tint.g, style.tint.b);
+        }
+        if current.alpha != style.alpha {
+            texture.set_alpha_mod(style.alpha);
+        }
+        if current.blend != style.blend {
+            texture.set_blend_mode(style.blend);
+        }
+        *current = style;
+        texture
+    }
+}
+
+/// Places rectangles of `sizes` on pages of at most `max_size` squared, in
+/// rows from the tallest down. Returns where each went and the size of every
+/// page.
+pub fn pack(sizes: &[(u32, u32)], max_size: u32) -> (Vec<AtlasRegion>, Vec<(u32, u32)>) {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| {
+        let (width, height) = sizes[i];
+        (std::cmp::Reverse(height), std::cmp::Reverse(width))
+    });
+
+    let mut regions = vec![
+        AtlasRegion {
+            page: 0,
+            rect: Rect::new(0, 0, 1, 1),
+        };
+        sizes.len()
+    ];
+    let mut pages: Vec<(u32, u32)> = Vec::new();
+    // Where the next image goes on the last page that is being filled
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    let mut filling: Option<usize> = None;
+
+    for i in order {
+        let (width, height) = sizes[i];
+        if width > max_size || height > max_size {
+            pages.push((width, height));
+            regions[i] = AtlasRegion {
+                page: pages.len() - 1,
+                rect: Rect::new(0, 0, width, height),
+            };
+            continue;
+        }
+
+        if filling.is_some() && x + width > max_size {
+            x = 0;
+            y += row_height + PADDING;
+            row_height = 0;
+        }
+        if filling.is_none() || y + height > max_size {
+            pages.push((0, 0));
+            filling = Some(pages.len() - 1);
+            x = 0;
+            y = 0;
+            row_height = 0;
+        }
+        let page = filling.expect("a page is being filled");
+
+        regions[i] = AtlasRegion {
+            page,
+            rect: Rect::new(x as i32, y as i32, width, height),
+        };
+        let (page_width, page_height) = &mut pages[page];
+        *page_width = (*page_width).max(x + width);
+        *page_height = (*page_height).max(y + height);
+        x += width + PADDING;
+        row_height = row_height.max(height);
+    }
+
+    (regions, pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: Rect, b: Rect) -> bool {
+        a.x() < b.x() + b.width() as i32
+            && b.x() < a.x() + a.width() as i32
+            && a.y() < b.y() + b.height() as i32
+            && b.y() < a.y() + a.height() as i32
+    }
+
+    #[test]
+    fn pack_keeps_sizes_inside_pages_without_overlap() {
+        let sizes = [
+            (64, 64),
+            (256, 256),
+            (16, 16),
+            (128, 32),
+            (100, 200),
+            (16, 16),
+        ];
+        let (regions, pages) = pack(&sizes, 300);
+
+        for (region, &(width, height)) in regions.iter().zip(sizes.iter()) {
+            assert_eq!((region.rect.width(), region.rect.height()), (width, height));
+            let (page_width, page_height) = pages[region.page];
+            assert!(region.rect.x() >= 0 && region.rect.y() >= 0);
+            assert!(region.rect.x() as u32 + width <= page_width);
+            assert!(region.rect.y() as u32 + height <= page_height);
+        }
+        for (i, a) in regions.iter().enumerate() {
+            for b in regions[i + 1..].iter() {
+                assert!(a.page != b.page || !overlaps(a.rect, b.rect));
+            }
+        }
+    }
+
+    #[test]
+    fn pack_opens_a_new_page_when_one_is_full() {
+        let (regions, pages) = pack(&[(200, 200), (200, 200), (200, 200)], 300);
+        assert_eq!(pages.len(), 3);
+        let mut used: Vec<_> = regions.iter().map(|region| region.page).collect();
+        used.sort_unstable();
+        assert_eq!(used, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn pack_gives_oversized_images_a_page_of_their_own() {
+        let (regions, pages) = pack(&[(16, 16), (4096, 64)], 2048);
+        assert_eq!(pages[regions[1].page], (4096, 64));
+        assert_ne!(regions[0].page, regions[1].page);
+        assert_eq!((regions[1].rect.x(), regions[1].rect.y()), (0, 0));
+    }
+}
@@ -0,0 +1,133 @@
+use specs::prelude::*;
+
+use crate::ecs::bullet::*;
+use crate::ecs::collision::*;
+use crate::ecs::components::*;
+use crate::ecs::pool::*;
+use crate::ecs::prefab::*;
+use crate::ecs::weapon::*;
+use crate::vec2::Vec2;
+
+const FRAMES: u32 = 600;
+const SHOTS_PER_FRAME: u32 = 500;
+
+/// Compares spawning and despawning projectiles through `LazyUpdate` against
+/// recycling them through the `ProjectilePool`. Run with `--bench-projectiles`.
+pub fn projectiles() {
+    let lazy = run_lazy();
+    let pooled = run_pooled();
+
+    let shots = (FRAMES * SHOTS_PER_FRAME) as f64;
+    println!(
+        "{} frames of {} shots spawned and despawned",
+        FRAMES, SHOTS_PER_FRAME
+    );
+    println!(
+        "lazy update: {:?} ({:.0} shots/s)",
+        lazy,
+        shots / lazy.as_secs_f64()
+    );
+    println!(
+        "pooled:      {:?} ({:.0} shots/s)",
+        pooled,
+        shots / pooled.as_secs_f64()
+    );
+}
+
+fn make_world() -> World {
+    let mut world = World::new();
+    System::setup(&mut ProjectilePoolSystem, &mut world);
+    world
+}
+
+fn make_spawn(prefabs: &Prefabs, weapon: &Weapon, bullet_types: &BulletTypes) -> ProjectileSpawn {
+    let collider = CircleCollider {
+        radius: 22.0,
+        layer: LayerMask::from_enum(Layers::Player),
+        collides_with: LayerMask::from_enum(Layers::Enemy),
+    };
+    bullet_spawn(
+        prefabs,
+        &Position::new(800.0, 800.0),
+        &collider,
+        weapon,
+        bullet_types.get(&weapon.bullet_type),
+        Vec2::up() * weapon.speed,
+        1.0,
+    )
+}
+
+fn run_lazy() -> std::time::Duration {
+    let mut world = make_world();
+    let weapon = Weapon::new(1400.0, 0.015, 25);
+    let bullet_types = BulletTypes::default();
+    let prefabs = Prefabs::load(crate::assets::PREFABS_PATH).expect("error loading prefabs");
+    let mut spawned = Vec::with_capacity(SHOTS_PER_FRAME as usize);
+
+    let start = std::time::Instant::now();
+    for _ in 0..FRAMES {
+        {
+            let entities = world.entities();
+            let lazy = world.read_resource::<LazyUpdate>();
+            for _ in 0..SHOTS_PER_FRAME {
+                let spawn = make_spawn(&prefabs, &weapon, &bullet_types);
+                let projectile = entities.create();
+                lazy.insert(projectile, Projectile {});
+                lazy.insert(projectile, spawn.position);
+                lazy.insert(projectile, spawn.sprite);
+                lazy.insert(projectile, spawn.velocity);
+                lazy.insert(projectile, spawn.collider);
+                lazy.insert(projectile, spawn.health);
+                lazy.insert(projectile, spawn.damage);
+                spawned.push(projectile);
+            }
+        }
+        world.maintain();
+
+        world
+            .delete_entities(&spawned)
+            .expect("error deleting benchmark projectiles");
+        spawned.clear();
+        world.maintain();
+    }
+    start.elapsed()
+}
+
+fn run_pooled() -> std::time::Duration {
+    let mut world = make_world();
+    let weapon = Weapon::new(1400.0, 0.015, 25);
+    let bullet_types = BulletTypes::default();
+    let prefabs = Prefabs::load(crate::assets::PREFABS_PATH).expect("error loading prefabs");
+    let mut system = ProjectilePoolSystem;
+
+    let start = std::time::Instant::now();
+    for _ in 0..FRAMES {
+        {
+            let mut pool = world.write_resource::<ProjectilePool>();
+            for _ in 0..SHOTS_PER_FRAME {
+                pool.request(make_spawn(&prefabs, &weapon, &bullet_types));
+            }
+        }
+        system.run_now(&world);
+        world.maintain();
+
+        {
+            let entities = world.entities();
+            let pooled_storage = world.read_storage::<Pooled>();
+            let position_storage = world.read_storage::<Position>();
+            let mut pool = world.write_resource::<ProjectilePool>();
+            for (entity, _, _) in (&entities, &pooled_storage, &position_storage).join() {
+                pool.release(entity);
+            }
+        }
+        system.run_now(&world);
+        world.maintain();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "pool recycled {} entities",
+        world.read_resource::<ProjectilePool>().free_count()
+    );
+    elapsed
+}
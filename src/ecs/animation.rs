@@ -64,3 +64,30 @@ impl<'a> System<'a> for AnimationSystem {
             .for_each(|(sprite, animation)| animation.update(delta_time, &mut sprite.src_rect));
     }
 }
+
+/// Fades a sprite out over the last `duration` seconds of its `Lifetime`
+#[derive(Component, Debug)]
+pub struct FadeOut {
+    pub duration: f32,
+}
+
+pub struct FadeSystem;
+
+impl<'a> System<'a> for FadeSystem {
+    type SystemData = (
+        WriteStorage<'a, Sprite>,
+        ReadStorage<'a, FadeOut>,
+        ReadStorage<'a, Lifetime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut sprite_storage, fade_storage, lifetime_storage) = data;
+
+        for (sprite, fade, lifetime) in
+            (&mut sprite_storage, &fade_storage, &lifetime_storage).join()
+        {
+            let left = (lifetime.time_left / fade.duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+            sprite.alpha = (left * u8::MAX as f32) as u8;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+use specs::prelude::*;
+
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// A tiled image that scrolls behind the playfield
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackgroundLayer {
+    /// Path of the spritesheet, as listed in `SPRITE_PATHS`
+    pub sprite: String,
+    #[serde(skip)]
+    pub spritesheet: usize,
+    /// Width and height of one tile on screen
+    pub size: (u32, u32),
+    /// Pixels per second the layer scrolls by on its own
+    pub velocity: Vec2,
+    /// How far the layer moves with the camera, 0 stays put and 1 moves like
+    /// the playfield
+    pub parallax: f32,
+    /// How far the layer has scrolled, kept within one tile
+    #[serde(skip)]
+    pub offset: Vec2,
+}
+
+/// Background layers, back to front
+#[derive(Default)]
+pub struct Background {
+    pub layers: Vec<BackgroundLayer>,
+}
+
+impl Background {
+    pub fn load(path: &str) -> Result<Background, String> {
+        let mut layers: Vec<BackgroundLayer> = crate::assets::load_ron(path)?;
+
+        for layer in layers.iter_mut() {
+            layer.spritesheet = crate::assets::sprite_id(&layer.sprite)
+                .ok_or_else(|| format!("{}: unknown sprite {}", path, layer.sprite))?;
+        }
+
+        Ok(Background { layers })
+    }
+}
+
+/// Scrolls the background layers
+pub struct BackgroundSystem;
+
+impl<'a> System<'a> for BackgroundSystem {
+    type SystemData = (Read<'a, DeltaTime>, Write<'a, Background>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, mut background) = data;
+        let delta_time = delta_time.0;
+
+        for layer in background.layers.iter_mut() {
+            let (width, height) = layer.size;
+            layer.offset += layer.velocity * delta_time;
+            // Wrapping keeps the offset small so it does not lose precision
+            // the longer the game runs
+            layer.offset.x = layer.offset.x.rem_euclid(width.max(1) as f32);
+            layer.offset.y = layer.offset.y.rem_euclid(height.max(1) as f32);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ecs::components::*;
+use crate::ecs::enemy::*;
+use crate::ecs::path::*;
+use crate::ecs::player::*;
+use crate::ecs::resources::*;
+use crate::ecs::weapon::*;
+use crate::vec2::Vec2;
+
+/// How an enemy moves while in a state
+#[derive(Clone, Debug, Deserialize)]
+pub enum Movement {
+    /// Follows a path relative to where the state was entered
+    FollowPath(PathConfig),
+    /// Stays in place
+    Hover,
+    /// Moves side to side over `width` pixels around where the state was entered
+    Strafe { speed: f32, width: f32 },
+    /// Backs away upwards
+    Retreat { speed: f32 },
+    /// Flies off in `direction` until it leaves the playfield
+    Exit { direction: Vec2, speed: f32 },
+    /// Follows the player sideways, staying at the same height
+    Track { speed: f32 },
+    /// Flies straight at the player
+    Chase { speed: f32 },
+    /// Backs away from the player while closer than `distance`
+    Avoid { speed: f32, distance: f32 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Condition {
+    /// Seconds spent in the state
+    After(f32),
+    /// Fraction of max health left
+    HealthBelow(f32),
+    PlayerCloserThan(f32),
+    PlayerFartherThan(f32),
+    PathFinished,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Transition {
+    pub when: Condition,
+    /// Name of the state to switch to
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BehaviorState {
+    pub name: String,
+    pub movement: Movement,
+    /// How to attack while in this state, `None` holds fire
+    #[serde(default)]
+    pub attack: Option<AttackConfig>,
+    #[serde(default)]
+    pub pattern: Option<FirePattern>,
+    /// How to aim while in this state, `None` keeps the current aim
+    #[serde(default)]
+    pub aim: Option<Aim>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+/// A state machine as written in data files, it starts in the first state
+#[derive(Clone, Debug, Deserialize)]
+pub struct BehaviorConfig {
+    pub states: Vec<BehaviorState>,
+}
+
+impl BehaviorConfig {
+    fn state_index(&self, name: &str) -> usize {
+        self.states
+            .iter()
+            .position(|state| state.name == name)
+            .expect("transitions are checked when behaviors load")
+    }
+}
+
+/// Behaviors by name, so stage files can refer to them
+#[derive(Default)]
+pub struct Behaviors {
+    behaviors: HashMap<String, Arc<BehaviorConfig>>,
+}
+
+impl Behaviors {
+    pub fn load(path: &str) -> Result<Behaviors, String> {
+        let behaviors: HashMap<String, BehaviorConfig> = crate::assets::load_ron(path)?;
+
+        // Catch typos in state names now rather than when the transition fires
+        for (name, config) in behaviors.iter() {
+            for state in config.states.iter() {
+                for transition in state.transitions.iter() {
+                    if !config
+                        .states
+                        .iter()
+                        .any(|target| target.name == transition.to)
+                    {
+                        return Err(format!(
+                            "{}: behavior {} has a transition from {} to unknown state {}",
+                            path, name, state.name, transition.to
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(Behaviors {
+            behaviors: behaviors
+                .into_iter()
+                .map(|(name, config)| (name, Arc::new(config)))
+                .collect(),
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.behaviors.contains_key(name)
+    }
+
+    /// Names are checked by `Bosses::validate` and `Stage::validate` at startup
+    pub fn get(&self, name: &str) -> Arc<BehaviorConfig> {
+        Arc::clone(&self.behaviors[name])
+    }
+}
+
+pub struct Behavior {
+    config: Arc<BehaviorConfig>,
+    current: usize,
+    entered: bool,
+    time_in_state: f32,
+    /// Where the current state was entered
+    anchor: Vec2,
+    strafe_direction: f32,
+}
+
+impl Component for Behavior {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Behavior {
+    pub fn new(config: Arc<BehaviorConfig>) -> Behavior {
+        Behavior {
+            config,
+            current: 0,
+            entered: false,
+            time_in_state: 0.0,
+            anchor: Vec2::default(),
+            strafe_direction: 1.0,
+        }
+    }
+
+    fn state(&self) -> &BehaviorState {
+        &self.config.states[self.current]
+    }
+}
+
+pub struct BehaviorSystem;
+
+impl<'a> System<'a> for BehaviorSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Health>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Enemy>,
+        WriteStorage<'a, Weapon>,
+        WriteStorage<'a, Path>,
+        WriteStorage<'a, Behavior>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            delta_time,
+            position_storage,
+            player_storage,
+            health_storage,
+            mut velocity_storage,
+            mut enemy_storage,
+            mut weapon_storage,
+            mut path_storage,
+            mut behavior_storage,
+        ) = data;
+        let delta_time = delta_time.0;
+
+        let player_position = (&position_storage, &player_storage)
+            .join()
+            .next()
+            .map(|(position, _)| position.position);
+
+        for (entity, position, velocity, enemy, weapon, behavior) in (
+            &entities,
+            &position_storage,
+            &mut velocity_storage,
+            &mut enemy_storage,
+            &mut weapon_storage,
+            &mut behavior_storage,
+        )
+            .join()
+        {
+            if !behavior.entered {
+                behavior.entered = true;
+                behavior.time_in_state = 0.0;
+                behavior.anchor = position.position;
+
+                let state = behavior.state();
+                enemy.set_attack(state.attack.clone());
+                if let Some(pattern) = state.pattern {
+                    weapon.pattern = pattern;
+                }
+                if let Some(aim) = state.aim {
+                    weapon.aim = aim;
+                }
+                match &state.movement {
+                    Movement::FollowPath(config) => {
+                        path_storage
+                            .insert(entity, Path::new(config.clone(), position.position))
+                            .expect("could not insert behavior path");
+                    }
+                    _ => {
+                        path_storage.remove(entity);
+                    }
+                }
+            }
+
+            behavior.time_in_state += delta_time;
+
+            match behavior.state().movement {
+                Movement::FollowPath(_) => {}
+                Movement::Hover => {
+                    velocity.velocity = Vec2::default();
+                }
+                Movement::Strafe { speed, width } => {
+                    let offset = position.position.x - behavior.anchor.x;
+                    if offset * behavior.strafe_direction > width / 2.0 {
+                        behavior.strafe_direction = -behavior.strafe_direction;
+                    }
+                    velocity.velocity = Vec2::right() * (speed * behavior.strafe_direction);
+                }
+                Movement::Retreat { speed } => {
+                    velocity.velocity = Vec2::up() * speed;
+                }
+                Movement::Exit { direction, speed } => {
+                    velocity.velocity = direction.normalzed() * speed;
+                }
+                Movement::Track { speed } => {
+                    let offset = player_position.map_or(0.0, |player| player.x - position.x());
+                    velocity.velocity = Vec2::right() * offset.clamp(-speed, speed);
+                }
+                Movement::Chase { speed } => {
+                    velocity.velocity = player_position
+                        .map(|player| player - position.position)
+                        .filter(|offset| offset.squared_length() > 1.0)
+                        .map_or(Vec2::default(), |offset| offset.normalzed() * speed);
+                }
+                Movement::Avoid { speed, distance } => {
+                    velocity.velocity = player_position
+                        .map(|player| position.position - player)
+                        .filter(|offset| {
+                            offset.squared_length() > 1.0 && offset.length() < distance
+                        })
+                        .map_or(Vec2::default(), |offset| offset.normalzed() * speed);
+                }
+            }
+
+            let health_fraction = health_storage.get(entity).map_or(1.0, |health| {
+                health.health as f32 / health.max_health.max(1) as f32
+            });
+            let player_distance =
+                player_position.map(|player| Vec2::distance(player, position.position));
+            let path_finished = path_storage.get(entity).is_none_or(|path| path.finished);
+
+            let next =
+                behavior
+                    .state()
+                    .transitions
+                    .iter()
+                    .find(|transition| match transition.when {
+                        Condition::After(seconds) => behavior.time_in_state >= seconds,
+                        Condition::HealthBelow(fraction) => health_fraction < fraction,
+                        Condition::PlayerCloserThan(distance) => {
+                            player_distance.is_some_and(|d| d < distance)
+                        }
+                        Condition::PlayerFartherThan(distance) => {
+                            player_distance.is_some_and(|d| d > distance)
+                        }
+                        Condition::PathFinished => path_finished,
+                    });
+
+            if let Some(transition) = next {
+                behavior.current = behavior.config.state_index(&transition.to);
+                behavior.entered = false;
+            }
+        }
+    }
+}
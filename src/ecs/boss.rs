@@ -0,0 +1,294 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ecs::behavior::*;
+use crate::ecs::bullet::BulletTypes;
+use crate::ecs::collision::*;
+use crate::ecs::components::*;
+use crate::ecs::enemy::*;
+use crate::ecs::path::*;
+use crate::ecs::pool::*;
+use crate::ecs::prefab::Prefabs;
+use crate::ecs::resources::*;
+use crate::ecs::weapon::*;
+use crate::vec2::Vec2;
+
+/// Bonus for beating a phase straight away, it shrinks as the phase's time
+/// limit runs out
+const BOSS_PHASE_BONUS: f32 = 20000.0;
+
+/// One stage of a boss fight
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossPhase {
+    /// The phase ends once health drops below this fraction of max health,
+    /// the last phase should use 0 so it lasts until the boss dies
+    pub until: f32,
+    /// Name of the behavior that drives the boss during this phase
+    pub behavior: String,
+    /// Path of the spritesheet, as listed in `SPRITE_PATHS`
+    pub sprite: String,
+    #[serde(skip)]
+    pub spritesheet: usize,
+    #[serde(default)]
+    pub bullet_type: Option<String>,
+    /// Seconds before the phase is skipped without being beaten
+    pub time_limit: f32,
+}
+
+/// A boss fight as written in data files
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossConfig {
+    pub phases: Vec<BossPhase>,
+    /// Behavior used to leave the stage when the last phase times out
+    pub escape: String,
+}
+
+/// Bosses by name, so stage files can refer to them
+#[derive(Default)]
+pub struct Bosses {
+    bosses: HashMap<String, Arc<BossConfig>>,
+}
+
+impl Bosses {
+    pub fn load(path: &str) -> Result<Bosses, String> {
+        let bosses: HashMap<String, BossConfig> = crate::assets::load_ron(path)?;
+
+        let mut loaded = HashMap::new();
+        for (name, mut config) in bosses {
+            for phase in config.phases.iter_mut() {
+                phase.spritesheet = crate::assets::sprite_id(&phase.sprite)
+                    .ok_or_else(|| format!("{}: unknown sprite {}", path, phase.sprite))?;
+            }
+            loaded.insert(name, Arc::new(config));
+        }
+
+        Ok(Bosses { bosses: loaded })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.bosses.contains_key(name)
+    }
+
+    /// Checks that every behavior and bullet type the bosses name exists
+    pub fn validate(
+        &self,
+        behaviors: &Behaviors,
+        bullet_types: &BulletTypes,
+    ) -> Result<(), String> {
+        for (name, config) in self.bosses.iter() {
+            for (index, phase) in config.phases.iter().enumerate() {
+                if !behaviors.contains(&phase.behavior) {
+                    return Err(format!(
+                        "boss {} phase {}: unknown behavior {}",
+                        name, index, phase.behavior
+                    ));
+                }
+                if let Some(bullet_type) = &phase.bullet_type {
+                    if !bullet_types.contains(bullet_type) {
+                        return Err(format!(
+                            "boss {} phase {}: unknown bullet type {}",
+                            name, index, bullet_type
+                        ));
+                    }
+                }
+            }
+            if !behaviors.contains(&config.escape) {
+                return Err(format!(
+                    "boss {}: unknown escape behavior {}",
+                    name, config.escape
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names are checked by `Stage::validate` at startup
+    pub fn get(&self, name: &str) -> Arc<BossConfig> {
+        Arc::clone(&self.bosses[name])
+    }
+}
+
+pub struct Boss {
+    config: Arc<BossConfig>,
+    phase: usize,
+    entered: bool,
+    phase_time: f32,
+    escaping: bool,
+}
+
+impl Component for Boss {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Boss {
+    pub fn new(config: Arc<BossConfig>) -> Boss {
+        Boss {
+            config,
+            phase: 0,
+            entered: false,
+            phase_time: 0.0,
+            escaping: false,
+        }
+    }
+
+    fn current_phase(&self) -> &BossPhase {
+        &self.config.phases[self.phase]
+    }
+
+    fn is_last_phase(&self) -> bool {
+        self.phase + 1 >= self.config.phases.len()
+    }
+
+    /// Health fractions at which phases end, for drawing the health bar
+    pub fn thresholds(&self) -> impl Iterator<Item = f32> + '_ {
+        self.config.phases.iter().map(|phase| phase.until)
+    }
+
+    /// Fraction of the current phase's time limit that is left
+    pub fn time_left(&self) -> f32 {
+        if self.escaping {
+            return 0.0;
+        }
+        let limit = self.current_phase().time_limit.max(f32::EPSILON);
+        (1.0 - self.phase_time / limit).max(0.0)
+    }
+}
+
+/// Moves bosses through their phases as they lose health or run out of time
+pub struct BossSystem;
+
+impl<'a> System<'a> for BossSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, Behaviors>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, CircleCollider>,
+        ReadStorage<'a, Pooled>,
+        Write<'a, ProjectilePool>,
+        WriteStorage<'a, Boss>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Sprite>,
+        WriteStorage<'a, Weapon>,
+        WriteStorage<'a, Behavior>,
+        WriteStorage<'a, Path>,
+        WriteStorage<'a, NoCull>,
+        Write<'a, EventChannel<GameEvent>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            delta_time,
+            behaviors,
+            position_storage,
+            projectile_storage,
+            collider_storage,
+            pooled_storage,
+            mut pool,
+            mut boss_storage,
+            mut health_storage,
+            mut sprite_storage,
+            mut weapon_storage,
+            mut behavior_storage,
+            mut path_storage,
+            mut no_cull_storage,
+            mut events,
+        ) = data;
+
+        let mut clear_bullets = false;
+
+        for (entity, boss) in (&entities, &mut boss_storage).join() {
+            if boss.escaping {
+                continue;
+            }
+
+            if !boss.entered {
+                boss.entered = true;
+                boss.phase_time = 0.0;
+
+                let phase = boss.current_phase();
+                if let Some(sprite) = sprite_storage.get_mut(entity) {
+                    sprite.spritesheet = phase.spritesheet;
+                }
+                if let (Some(weapon), Some(bullet_type)) =
+                    (weapon_storage.get_mut(entity), &phase.bullet_type)
+                {
+                    weapon.bullet_type = bullet_type.clone();
+                }
+                behavior_storage
+                    .insert(entity, Behavior::new(behaviors.get(&phase.behavior)))
+                    .expect("could not insert boss behavior");
+            }
+
+            boss.phase_time += delta_time.0;
+
+            let health = match health_storage.get_mut(entity) {
+                Some(health) => health,
+                None => continue,
+            };
+            let threshold = boss.current_phase().until;
+            let beaten = !boss.is_last_phase()
+                && (health.health as f32) < threshold * health.max_health as f32;
+            let timed_out = boss.phase_time >= boss.current_phase().time_limit;
+
+            if !beaten && !timed_out {
+                continue;
+            }
+            clear_bullets = true;
+
+            if boss.is_last_phase() {
+                if timed_out {
+                    // The boss gets away, it can no longer be hit and leaves the stage
+                    boss.escaping = true;
+                    health_storage.remove(entity);
+                    no_cull_storage.remove(entity);
+                    path_storage.remove(entity);
+                    behavior_storage
+                        .insert(entity, Behavior::new(behaviors.get(&boss.config.escape)))
+                        .expect("could not insert boss behavior");
+                }
+                continue;
+            }
+
+            if timed_out {
+                // Skipping a phase costs the boss the health that phase was worth
+                let skipped = (threshold * health.max_health as f32) as u32;
+                health.health = health.health.min(skipped.max(1));
+            } else if let Some(position) = position_storage.get(entity) {
+                let bonus = (BOSS_PHASE_BONUS * boss.time_left()) as u32;
+                events.single_write(GameEvent::Bonus(position.position, bonus));
+            }
+            boss.phase += 1;
+            boss.entered = false;
+        }
+
+        if clear_bullets {
+            clear_enemy_bullets(
+                &entities,
+                &projectile_storage,
+                &collider_storage,
+                &pooled_storage,
+                &mut pool,
+            );
+        }
+    }
+}
+
+/// Bigger send-off than a regular enemy, a ring of explosions
+pub fn on_boss_ded(
+    position: Vec2,
+    prefabs: &Prefabs,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) {
+    for i in 0..8 {
+        let offset = Vec2::right().rotated(i as f32 * std::f32::consts::PI / 4.0) * 48.0;
+        on_enemy_ded(position + offset, prefabs, entities, world);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use sdl2::rect::Rect;
+
+use crate::assets::*;
+use crate::ecs::components::SpriteBlend;
+
+pub const PLAYER_BULLET: &str = "player";
+
+/// How a kind of bullet looks and hits
+#[derive(Clone, Debug)]
+pub struct BulletType {
+    pub spritesheet: usize,
+    /// Region of the spritesheet the bullet is drawn from
+    pub src_rect: Rect,
+    /// Size in pixels on screen
+    pub size: i32,
+    pub collider_radius: f32,
+    /// Extra damage dealt on top of the firing weapon's damage
+    pub damage: u32,
+    /// Whether the sprite turns to face the way the bullet flies
+    pub align_to_velocity: bool,
+    pub blend: SpriteBlend,
+}
+
+impl BulletType {
+    fn single(sprite: &str, size: i32, damage: u32) -> BulletType {
+        BulletType {
+            spritesheet: sprite_id(sprite).expect("bullet sprites are listed in SPRITE_PATHS"),
+            src_rect: Rect::new(0, 0, 16, 16),
+            size,
+            collider_radius: size as f32 / 2.0,
+            damage,
+            align_to_velocity: true,
+            blend: SpriteBlend::Blend,
+        }
+    }
+}
+
+/// Bullet types by name, so weapons and patterns can refer to them
+pub struct BulletTypes {
+    types: HashMap<String, BulletType>,
+}
+
+impl BulletTypes {
+    pub fn register(&mut self, name: &str, bullet_type: BulletType) {
+        self.types.insert(name.to_string(), bullet_type);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    /// Names are checked by `Prefabs::validate` and `Bosses::validate` at
+    /// startup
+    pub fn get(&self, name: &str) -> &BulletType {
+        &self.types[name]
+    }
+}
+
+impl Default for BulletTypes {
+    fn default() -> Self {
+        let mut bullet_types = BulletTypes {
+            types: HashMap::new(),
+        };
+
+        bullet_types.register(
+            PLAYER_BULLET,
+            BulletType {
+                collider_radius: 16.0,
+                ..BulletType::single(BULLET_SPRITE_PATH, BULLET_SIZE, 0)
+            },
+        );
+        bullet_types.register(
+            "red",
+            BulletType::single(BULLET_RED_SPRITE_PATH, BULLET_SIZE, 0),
+        );
+        bullet_types.register(
+            "cyan",
+            BulletType::single(BULLET_CYAN_SPRITE_PATH, BULLET_SIZE, 0),
+        );
+        bullet_types.register(
+            "green",
+            BulletType::single(BULLET_GREEN_SPRITE_PATH, BULLET_SIZE, 0),
+        );
+        bullet_types.register(
+            "purple",
+            BulletType::single(BULLET_PURPLE_SPRITE_PATH, BULLET_SIZE, 0),
+        );
+        bullet_types.register(
+            "yellow",
+            BulletType::single(BULLET_YELLOW_SPRITE_PATH, BULLET_SIZE, 0),
+        );
+
+        // The sheet holds 4x2 cells of 16x16 orbs, they glow over what is
+        // underneath
+        for index in 0..8 {
+            bullet_types.register(
+                &format!("orb{}", index),
+                BulletType {
+                    src_rect: Rect::new((index % 4) * 16, (index / 4) * 16, 16, 16),
+                    blend: SpriteBlend::Additive,
+                    ..BulletType::single(BULLET_SHEET_SPRITE_PATH, 24, 5)
+                },
+            );
+        }
+
+        bullet_types
+    }
+}
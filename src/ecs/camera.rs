@@ -0,0 +1,142 @@
+use rand::Rng;
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// Trauma lost per second, shaking stops once it reaches 0
+const TRAUMA_DECAY: f32 = 1.2;
+/// Furthest the view is thrown off at full trauma, in pixels
+const MAX_SHAKE_OFFSET: f32 = 24.0;
+const TRAUMA_ON_PLAYER_DEATH: f32 = 0.7;
+
+/// The part of the world that is drawn. World positions are transformed to
+/// screen positions by `world_to_screen`.
+pub struct Camera {
+    /// World position in the middle of the screen
+    pub position: Vec2,
+    /// Moves `position` every frame, in pixels per second
+    pub scroll: Vec2,
+    /// Screen pixels per world pixel
+    pub zoom: f32,
+    /// Area the view is kept inside of, unless shaking
+    pub bounds: Option<(Vec2, Vec2)>,
+    viewport: Vec2,
+    /// Between 0 and 1, how hard the screen is shaking
+    trauma: f32,
+    shake: Vec2,
+}
+
+impl Camera {
+    /// A camera showing exactly the `width` by `height` area from the origin
+    pub fn new(width: f32, height: f32) -> Camera {
+        let viewport = Vec2 {
+            x: width,
+            y: height,
+        };
+        Camera {
+            position: viewport * 0.5,
+            scroll: Vec2::default(),
+            zoom: 1.0,
+            bounds: None,
+            viewport,
+            trauma: 0.0,
+            shake: Vec2::default(),
+        }
+    }
+
+    pub fn with_bounds(mut self, min: Vec2, max: Vec2) -> Camera {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// Shakes the screen, adding up to a trauma of 1
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    pub fn world_to_screen(&self, position: Vec2) -> Vec2 {
+        (position - self.position - self.shake) * self.zoom + self.viewport * 0.5
+    }
+
+    pub fn screen_to_world(&self, position: Vec2) -> Vec2 {
+        (position - self.viewport * 0.5) * (1.0 / self.zoom.max(f32::EPSILON))
+            + self.position
+            + self.shake
+    }
+
+    /// How far the camera has moved a background layer on screen, where
+    /// `parallax` 0 never moves and 1 moves as much as the playfield
+    pub fn parallax_offset(&self, parallax: f32) -> Vec2 {
+        (self.viewport * 0.5 - self.position - self.shake) * parallax
+    }
+
+    /// Keeps as much of the view inside the bounds as fits, centering it on
+    /// axes where the bounds are smaller than the view
+    fn clamp_to_bounds(&mut self) {
+        let (min, max) = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let half_view = self.viewport * (0.5 / self.zoom.max(f32::EPSILON));
+        let clamp_axis = |value: f32, min: f32, max: f32, half: f32| {
+            if max - min <= half * 2.0 {
+                (min + max) / 2.0
+            } else {
+                value.max(min + half).min(max - half)
+            }
+        };
+        self.position.x = clamp_axis(self.position.x, min.x, max.x, half_view.x);
+        self.position.y = clamp_axis(self.position.y, min.y, max.y, half_view.y);
+    }
+}
+
+/// Scrolls the camera and shakes it when big things happen
+#[derive(Default)]
+pub struct CameraSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'a> System<'a> for CameraSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        Read<'a, EventChannel<GameEvent>>,
+        WriteExpect<'a, Camera>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, events, mut camera) = data;
+        let delta_time = delta_time.0;
+
+        let reader = self.reader.as_mut().expect("CameraSystem was not set up");
+        for event in events.read(reader) {
+            if let GameEvent::PlayerDied(_) = event {
+                camera.add_trauma(TRAUMA_ON_PLAYER_DEATH);
+            }
+        }
+
+        let scroll = camera.scroll * delta_time;
+        camera.position += scroll;
+        camera.clamp_to_bounds();
+
+        camera.trauma = (camera.trauma - TRAUMA_DECAY * delta_time).max(0.0);
+        // Squaring makes small amounts of trauma barely noticeable and large
+        // amounts violent
+        let shake = camera.trauma * camera.trauma * MAX_SHAKE_OFFSET;
+        let mut rng = rand::thread_rng();
+        camera.shake = Vec2 {
+            x: rng.gen_range(-1.0, 1.0) * shake,
+            y: rng.gen_range(-1.0, 1.0) * shake,
+        };
+    }
+}
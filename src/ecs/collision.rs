@@ -1,3 +1,4 @@
+use serde::Deserialize;
 use specs::prelude::*;
 use specs_derive::Component;
 
@@ -10,7 +11,7 @@ pub struct CircleCollider {
     pub collides_with: LayerMask,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Deserialize)]
 pub enum Layers {
     Player = 0x01,
     Enemy = 0x02,
@@ -42,8 +43,7 @@ impl LayerMask {
         }
     }
 
-    #[allow(dead_code)]
-    fn any(&self, other: &LayerMask) -> bool {
+    pub fn any(&self, other: &LayerMask) -> bool {
         self.bitmask & other.bitmask != 0x00
     }
 
@@ -1,4 +1,6 @@
+use crate::ecs::prefab::Prefabs;
 use crate::vec2::Vec2;
+use serde::Deserialize;
 use specs::prelude::*;
 use specs_derive::Component;
 
@@ -29,22 +31,36 @@ pub struct Lifetime {
     pub time_left: f32,
 }
 
+/// Opts an entity out of being despawned when it leaves the playfield
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct NoCull;
+
+/// Draws a sprite in front of others with the same layer and `z` the further
+/// down the screen it is
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct YSort;
+
 #[derive(Copy, Clone)]
 pub enum DamageEvent {
     DamageTaken(u32, Vec2),
 }
 
+/// Called with where an entity died, to spawn whatever it leaves behind
+pub type DeathHandler = fn(position: Vec2, &Prefabs, &specs::Entities, &specs::LazyUpdate);
+
 #[derive(Component)]
 pub struct Health {
     pub health: u32,
     pub max_health: u32,
     pub damage_events: Vec<DamageEvent>,
-    pub on_death: fn(position: Vec2, &specs::Entities, &specs::LazyUpdate),
+    pub on_death: DeathHandler,
 }
 
 impl Health {
     #[allow(unused)]
-    pub fn new(health: u32, on_death: fn(Vec2, &specs::Entities, &specs::LazyUpdate)) -> Health {
+    pub fn new(health: u32, on_death: DeathHandler) -> Health {
         Health {
             health,
             max_health: health,
@@ -59,7 +75,7 @@ impl Health {
             health,
             max_health: health,
             damage_events: Vec::new(),
-            on_death: |_, _, _| {},
+            on_death: |_, _, _, _| {},
         }
     }
     #[allow(unused)]
@@ -67,6 +83,22 @@ impl Health {
         self.damage_events
             .push(DamageEvent::DamageTaken(damage.damage, location));
     }
+
+    /// Fraction of max health left
+    pub fn fraction(&self) -> f32 {
+        self.health as f32 / self.max_health.max(1) as f32
+    }
+}
+
+/// Draws a bar above an entity with `Health` showing how much it has left,
+/// hidden while at full health
+#[derive(Component, Debug)]
+#[storage(HashMapStorage)]
+pub struct HealthBar {
+    pub width: u32,
+    pub height: u32,
+    /// Distance from the entity's position up to the bar
+    pub offset: f32,
 }
 
 #[derive(Component, Debug)]
@@ -93,6 +125,7 @@ pub struct Velocity {
 }
 
 impl Velocity {
+    #[allow(dead_code)]
     pub fn new(x: f32, y: f32) -> Self {
         Velocity {
             velocity: Vec2 { x, y },
@@ -121,6 +154,28 @@ pub struct Name {
     pub name: String,
 }
 
+/// Groups of sprites drawn back to front, in the order listed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum RenderLayer {
+    Background,
+    Enemies,
+    Bullets,
+    Player,
+    Effects,
+    Hud,
+}
+
+/// How a sprite's colors combine with what is already drawn
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum SpriteBlend {
+    /// Regular alpha blending
+    Blend,
+    /// Adds to the colors underneath, for glows
+    Additive,
+    /// Multiplies the colors underneath
+    Modulate,
+}
+
 #[derive(Component, Debug, Clone)]
 #[storage(VecStorage)]
 pub struct Sprite {
@@ -130,4 +185,50 @@ pub struct Sprite {
     pub src_rect: sdl2::rect::Rect,
     /// Size in pixels on screen
     pub size: sdl2::rect::Point,
+    pub layer: RenderLayer,
+    /// Draw order within the layer, higher is drawn in front
+    pub z: f32,
+    /// Degrees clockwise
+    pub rotation: f32,
+    /// Turns the sprite to face the way its entity moves, on top of
+    /// `rotation`. Sprites are drawn facing up.
+    pub align_to_velocity: bool,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Multiplier on `size` per axis
+    pub scale: Vec2,
+    /// Point of the sprite placed on the entity's position and rotated
+    /// around, as a fraction of its size
+    pub pivot: Vec2,
+    /// Multiplied with the sprite's colors, white leaves them as they are
+    pub tint: sdl2::pixels::Color,
+    pub alpha: u8,
+    pub blend: SpriteBlend,
+}
+
+impl Sprite {
+    /// An unrotated, unscaled, untinted sprite centered on its entity
+    pub fn new(
+        spritesheet: usize,
+        src_rect: sdl2::rect::Rect,
+        size: sdl2::rect::Point,
+        layer: RenderLayer,
+    ) -> Sprite {
+        Sprite {
+            spritesheet,
+            src_rect,
+            size,
+            layer,
+            z: 0.0,
+            rotation: 0.0,
+            align_to_velocity: false,
+            flip_x: false,
+            flip_y: false,
+            scale: Vec2 { x: 1.0, y: 1.0 },
+            pivot: Vec2 { x: 0.5, y: 0.5 },
+            tint: sdl2::pixels::Color::WHITE,
+            alpha: 255,
+            blend: SpriteBlend::Blend,
+        }
+    }
 }
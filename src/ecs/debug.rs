@@ -0,0 +1,47 @@
+use sdl2::keyboard::Scancode;
+use specs::prelude::*;
+
+use crate::ecs::resources::*;
+
+/// Size of the cells of the grid the debug overlay draws over the world
+pub const DEBUG_GRID_CELL: f32 = 100.0;
+/// Seconds of movement the velocity lines of the debug overlay show
+pub const DEBUG_VELOCITY_SCALE: f32 = 0.1;
+
+/// Whether colliders, velocities, entity ids and the grid are drawn over the
+/// game for tuning
+pub struct DebugOverlay {
+    pub enabled: bool,
+    /// Whether F3 can turn the overlay on, always in debug builds
+    pub available: bool,
+}
+
+impl DebugOverlay {
+    pub fn new(allow_in_release: bool) -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            available: cfg!(debug_assertions) || allow_in_release,
+        }
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay::new(false)
+    }
+}
+
+/// Toggles the debug overlay with F3
+pub struct DebugOverlaySystem;
+
+impl<'a> System<'a> for DebugOverlaySystem {
+    type SystemData = (Read<'a, InputResource>, Write<'a, DebugOverlay>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (input, mut overlay) = data;
+
+        if overlay.available && input.0.get_key(Scancode::F3).pressed {
+            overlay.enabled = !overlay.enabled;
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use serde::Deserialize;
+
+/// Multipliers and starting resources of a difficulty preset
+#[derive(Clone, Debug, Deserialize)]
+pub struct Difficulty {
+    pub name: String,
+    /// Multiplier on the health enemies spawn with
+    pub enemy_health: f32,
+    /// Multiplier on the speed of enemy bullets
+    pub bullet_speed: f32,
+    /// Multiplier on enemy weapon and attack cooldowns, lower fires more often
+    pub fire_cooldown: f32,
+    /// Lives the player gets before the game is over
+    pub lives: u32,
+    /// Bombs the player gets with every life
+    pub bombs: u32,
+}
+
+impl Difficulty {
+    pub fn load(path: &str) -> Result<Difficulty, String> {
+        crate::assets::load_ron(path)
+    }
+
+    /// Loads the preset called `name` from `DIFFICULTY_PATHS`
+    pub fn load_preset(name: &str) -> Result<Difficulty, String> {
+        let (_, path) = crate::assets::DIFFICULTY_PATHS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let presets: Vec<_> = crate::assets::DIFFICULTY_PATHS
+                    .iter()
+                    .map(|(preset, _)| *preset)
+                    .collect();
+                format!(
+                    "unknown difficulty {}, expected one of {}",
+                    name,
+                    presets.join(", ")
+                )
+            })?;
+        Difficulty::load(path)
+    }
+}
@@ -1,9 +1,21 @@
 use crate::ecs::resources::DeltaTime;
 use rand::Rng;
+use serde::Deserialize;
 use specs::prelude::*;
+use specs::shrev::EventChannel;
+use std::collections::HashMap;
 
+use crate::ecs::behavior::*;
+use crate::ecs::boss::*;
 use crate::ecs::components::*;
+use crate::ecs::difficulty::*;
+use crate::ecs::formation::*;
+use crate::ecs::path::*;
 use crate::ecs::player::*;
+use crate::ecs::prefab::*;
+use crate::ecs::rank::*;
+use crate::ecs::resources::GameEvent;
+use crate::ecs::stage::*;
 use crate::ecs::weapon::*;
 use crate::vec2::Vec2;
 
@@ -40,7 +52,7 @@ enum EnemyMoveState {
     Moving(Direction, Vec2),
 }
 
-#[allow(unused)]
+#[derive(Clone, Debug, Deserialize)]
 pub enum AttackConfig {
     Constant(f32, u32),
     Random(f32, f32, u32),
@@ -49,39 +61,74 @@ pub enum AttackConfig {
 pub struct Enemy {
     speed: f32,
     move_state: EnemyMoveState,
-    attack_config: AttackConfig,
+    attack_config: Option<AttackConfig>,
     attack_cooldown: f32,
+    /// Multiplier on the cooldowns of the attack config
+    cooldown_scale: f32,
+    /// Score for killing this enemy, before the chain multiplier
+    pub points: u32,
 }
 
-impl Enemy {
-    pub fn new(speed: f32, attack_config: AttackConfig) -> Enemy {
-        let attack_cooldown: f32;
-        match attack_config {
-            AttackConfig::Constant(cooldown, _) => {
-                attack_cooldown = cooldown;
-            }
+impl AttackConfig {
+    /// Cooldown before the first attack
+    fn first_cooldown(&self) -> f32 {
+        match *self {
+            AttackConfig::Constant(cooldown, _) => cooldown,
             AttackConfig::Random(_, max_cooldown, _) => {
-                attack_cooldown = rand::thread_rng().gen_range(0.0, max_cooldown);
+                rand::thread_rng().gen_range(0.0, max_cooldown)
             }
         }
+    }
+}
+
+impl Enemy {
+    /// An enemy attacking with `attack_config`, `None` holds fire
+    pub fn new(speed: f32, attack_config: Option<AttackConfig>) -> Enemy {
         Enemy {
             speed,
             move_state: EnemyMoveState::Idle,
+            attack_cooldown: attack_config
+                .as_ref()
+                .map_or(0.0, AttackConfig::first_cooldown),
             attack_config,
-            attack_cooldown,
+            cooldown_scale: 1.0,
+            points: 0,
         }
     }
 
-    fn fire(&mut self, weapon: &mut Weapon) {
+    pub fn with_cooldown_scale(mut self, scale: f32) -> Enemy {
+        self.cooldown_scale = scale;
+        self.attack_cooldown *= scale;
+        self
+    }
+
+    pub fn with_points(mut self, points: u32) -> Enemy {
+        self.points = points;
+        self
+    }
+
+    /// Changes how the enemy attacks, `None` holds fire
+    pub fn set_attack(&mut self, attack_config: Option<AttackConfig>) {
+        if let Some(config) = &attack_config {
+            self.attack_cooldown = config.first_cooldown() * self.cooldown_scale;
+        }
+        self.attack_config = attack_config;
+    }
+
+    /// Starts an attack, cooldowns are divided by `fire_rate`
+    fn fire(&mut self, weapon: &mut Weapon, fire_rate: f32) {
+        let scale = self.cooldown_scale / fire_rate;
         match self.attack_config {
-            AttackConfig::Constant(cooldown, amount) => {
+            Some(AttackConfig::Constant(cooldown, amount)) => {
                 weapon.command = WeaponFireCommand::FireAmount(amount);
-                self.attack_cooldown = cooldown;
+                self.attack_cooldown = cooldown * scale;
             }
-            AttackConfig::Random(min_cooldown, max_cooldown, amount) => {
+            Some(AttackConfig::Random(min_cooldown, max_cooldown, amount)) => {
                 weapon.command = WeaponFireCommand::FireAmount(amount);
-                self.attack_cooldown = rand::thread_rng().gen_range(min_cooldown, max_cooldown);
+                self.attack_cooldown =
+                    rand::thread_rng().gen_range(min_cooldown, max_cooldown) * scale;
             }
+            None => {}
         }
     }
 }
@@ -96,163 +143,284 @@ impl<'a> System<'a> for EnemySystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
         Read<'a, DeltaTime>,
+        Read<'a, Rank>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Player>,
         WriteStorage<'a, Enemy>,
         WriteStorage<'a, Velocity>,
         WriteStorage<'a, Weapon>,
+        ReadStorage<'a, Path>,
+        ReadStorage<'a, Behavior>,
+        ReadStorage<'a, FormationMember>,
     );
 
-    #[allow(unused_variables)]
     fn run(&mut self, data: Self::SystemData) {
         let (
             delta_time,
+            rank,
             position_storage,
             player_storage,
             mut enemy_storage,
             mut velocity_storage,
             mut weapon_storage,
+            path_storage,
+            behavior_storage,
+            member_storage,
         ) = data;
         let delta_time = delta_time.0;
 
-        for (enemy_pos, mut enemy_compoent, mut enemy_velocity, weapon) in (
+        let player_target = (&position_storage, &velocity_storage, &player_storage)
+            .join()
+            .next()
+            .map(|(position, velocity, _)| Target {
+                position: position.position,
+                velocity: velocity.velocity,
+            });
+
+        for (enemy_pos, mut enemy_compoent, mut enemy_velocity, weapon, path, behavior, member) in (
             &position_storage,
             &mut enemy_storage,
             &mut velocity_storage,
             &mut weapon_storage,
+            path_storage.maybe(),
+            behavior_storage.maybe(),
+            member_storage.maybe(),
         )
             .join()
         {
+            weapon.target = player_target;
             enemy_compoent.attack_cooldown -= delta_time;
 
             if enemy_compoent.attack_cooldown <= 0.0 {
-                enemy_compoent.fire(weapon);
+                enemy_compoent.fire(weapon, rank.fire_rate());
             }
 
-            match enemy_compoent.move_state {
+            // Enemies on a path leave the steering to the PathSystem until it
+            // ends, enemies with a behavior or in a formation leave it to the
+            // BehaviorSystem or FormationSystem
+            if behavior.is_some() || member.is_some() || path.is_some_and(|path| !path.finished) {
+                continue;
+            }
+
+            let mut next_state = None;
+            match &mut enemy_compoent.move_state {
                 EnemyMoveState::Idle => {
                     let target = enemy_pos.position + Vec2::right() * 100.0;
-                    enemy_compoent.move_state = EnemyMoveState::Moving(Direction::Right, target);
+                    next_state = Some(EnemyMoveState::Moving(Direction::Right, target));
                 }
 
                 EnemyMoveState::Moving(direction, target) => {
-                    let dot_prod = Vec2::dot(&(target - enemy_pos.position), &direction.to_vec());
+                    let dot_prod = Vec2::dot(&(*target - enemy_pos.position), &direction.to_vec());
 
                     let new_vel = direction.to_vec() * enemy_compoent.speed;
                     enemy_velocity.velocity = new_vel;
 
                     if dot_prod < 0.0 {
                         let new_direction = direction.next();
-                        let new_target = target + new_direction.to_vec() * 100.0;
-                        enemy_compoent.move_state =
-                            EnemyMoveState::Moving(new_direction, new_target);
+                        let new_target = *target + new_direction.to_vec() * 100.0;
+                        next_state = Some(EnemyMoveState::Moving(new_direction, new_target));
                     }
                 }
             }
+
+            if let Some(next_state) = next_state {
+                enemy_compoent.move_state = next_state;
+            }
         }
     }
 }
 
-#[derive(Default)]
+/// Plays back the spawn events of a stage, and restarts it once it is cleared
 pub struct EnemySpawnerSystem {
-    wave_counter: u32,
+    timeline: Vec<ScheduledSpawn>,
+    next_spawn: usize,
+    stage_time: f32,
+    loop_counter: u32,
+    /// Anchors of the formations spawned this loop, by event index
+    formations: HashMap<usize, Entity>,
 }
 
 impl EnemySpawnerSystem {
-    pub fn default() -> Self {
-        EnemySpawnerSystem { wave_counter: 0 }
+    pub fn new(stage: &Stage) -> Self {
+        EnemySpawnerSystem {
+            timeline: stage.timeline(),
+            next_spawn: 0,
+            stage_time: 0.0,
+            loop_counter: 0,
+            formations: HashMap::new(),
+        }
     }
 }
 
 impl<'a> System<'a> for EnemySpawnerSystem {
-    type SystemData = (Entities<'a>, ReadStorage<'a, Enemy>, Read<'a, LazyUpdate>);
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Enemy>,
+        Read<'a, Prefabs>,
+        Read<'a, Behaviors>,
+        Read<'a, Bosses>,
+        Read<'a, Rank>,
+        ReadExpect<'a, Difficulty>,
+        Write<'a, EventChannel<GameEvent>>,
+        Read<'a, LazyUpdate>,
+    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, enemy_storage, world) = data;
+        let (
+            entities,
+            delta_time,
+            enemy_storage,
+            prefabs,
+            behaviors,
+            bosses,
+            rank,
+            difficulty,
+            mut events,
+            world,
+        ) = data;
 
-        let mut enemy_count = 0;
+        self.stage_time += delta_time.0;
 
-        for _ in (enemy_storage).join() {
-            enemy_count += 1;
+        // Spawns only show up in `enemy_storage` next frame, so the stage is
+        // not checked for being cleared in a frame that spawned anything
+        let mut spawned = false;
+        while let Some(spawn) = self.timeline.get(self.next_spawn) {
+            if spawn.time > self.stage_time {
+                break;
+            }
+            // The formation system deletes an anchor once its members are all
+            // dead, so later members of the group start a new one
+            let formations = &mut self.formations;
+            let formation = spawn.event.formation.as_ref().map(|config| {
+                let anchor = formations
+                    .entry(spawn.group)
+                    .or_insert_with(|| spawn_formation(spawn, config, &entities, &world));
+                if !entities.is_alive(*anchor) {
+                    *anchor = spawn_formation(spawn, config, &entities, &world);
+                }
+                *anchor
+            });
+            spawn_enemy(
+                spawn,
+                formation,
+                &prefabs,
+                &behaviors,
+                &bosses,
+                &rank,
+                &difficulty,
+                &entities,
+                &world,
+            );
+            self.next_spawn += 1;
+            spawned = true;
         }
 
-        let mut enemy_spawned_emount = 0;
-        if enemy_count == 0 {
-            self.wave_counter += 1;
-
-            for x in 0..(16 * self.wave_counter) {
-                for y in 0..(4 * self.wave_counter) {
-                    let new_enemy = entities.create();
-                    enemy_spawned_emount += 1;
-                    world.insert(
-                        new_enemy,
-                        Position::new(
-                            (100 + x * 80 / self.wave_counter) as f32,
-                            (100 + y * 80 / self.wave_counter) as f32,
-                        ),
-                    );
-
-                    world.insert(
-                        new_enemy,
-                        Sprite {
-                            spritesheet: crate::assets::BOSS_SPRITE_ID,
-                            size: sdl2::rect::Point::new(
-                                64 / self.wave_counter as i32,
-                                64 / self.wave_counter as i32,
-                            ),
-                            src_rect: sdl2::rect::Rect::new(0, 0, 128, 128),
-                        },
-                    );
-
-                    world.insert(new_enemy, Velocity::default());
-
-                    world.insert(
-                        new_enemy,
-                        crate::ecs::collision::CircleCollider {
-                            radius: 32.0,
-                            layer: crate::ecs::collision::LayerMask::from_enum(
-                                crate::ecs::collision::Layers::Enemy,
-                            ),
-                            collides_with: crate::ecs::collision::LayerMask::from_enum(
-                                crate::ecs::collision::Layers::Player,
-                            ),
-                        },
-                    );
-
-                    world.insert(new_enemy, Health::new(25, on_enemy_ded));
-
-                    world.insert(new_enemy, Damage::new(5));
-
-                    world.insert(
-                        new_enemy,
-                        Enemy::new(100.0, AttackConfig::Random(5.0, 15.0, 3)),
-                    );
-
-                    world.insert(new_enemy, Weapon::new(-800.0, 0.5, 5));
-                }
-            }
-            println!("Spawned {:?} new enemies", enemy_spawned_emount);
+        let stage_done = self.next_spawn == self.timeline.len();
+        if stage_done && !spawned && enemy_storage.join().next().is_none() {
+            self.loop_counter += 1;
+            self.next_spawn = 0;
+            self.stage_time = 0.0;
+            self.formations.clear();
+            events.single_write(GameEvent::StageLooped(self.loop_counter));
         }
     }
 }
 
-fn on_enemy_ded(position: Vec2, entities: &specs::Entities, world: &specs::LazyUpdate) {
-    let explosion = entities.create();
+fn spawn_formation(
+    spawn: &ScheduledSpawn,
+    config: &FormationConfig,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) -> Entity {
+    // The anchor starts in the middle of where its members spawn
+    let event = &spawn.event;
+    let center = event.position + event.spacing * ((event.count.max(1) - 1) as f32 / 2.0);
+
+    let anchor = entities.create();
+    world.insert(anchor, Position { position: center });
+    world.insert(anchor, Velocity::default());
+    world.insert(anchor, NoCull);
+    if let Some(path) = &config.path {
+        world.insert(anchor, Path::new(path.clone(), center));
+    }
+    world.insert(anchor, Formation::new(config.clone()));
+    anchor
+}
 
-    world.insert(
-        explosion,
-        Position {
-            position: Vec2::randomize(position, 10.0),
-        },
-    );
-    world.insert(
-        explosion,
-        Sprite {
-            spritesheet: crate::assets::EXPLOSION_SPRITE_ID,
-            size: sdl2::rect::Point::new(64, 64),
-            src_rect: sdl2::rect::Rect::new(0, 0, 64, 64),
-        },
+#[allow(clippy::too_many_arguments)]
+fn spawn_enemy(
+    spawn: &ScheduledSpawn,
+    formation: Option<Entity>,
+    prefabs: &Prefabs,
+    behaviors: &Behaviors,
+    bosses: &Bosses,
+    rank: &Rank,
+    difficulty: &Difficulty,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) {
+    let event = &spawn.event;
+    let new_enemy =
+        prefabs.spawn_with(&event.enemy, spawn.position, entities, world, |component| {
+            match component {
+                ComponentData::Health { health, .. } => {
+                    *health =
+                        (*health as f32 * difficulty.enemy_health * rank.enemy_health()) as u32;
+                }
+                ComponentData::Enemy {
+                    attack,
+                    cooldown_scale,
+                    ..
+                } => {
+                    *attack = Some(event.attack.clone());
+                    *cooldown_scale *= difficulty.fire_cooldown;
+                }
+                ComponentData::Weapon {
+                    speed,
+                    time_between_shots,
+                    aim,
+                    ..
+                } => {
+                    *speed *= difficulty.bullet_speed;
+                    *time_between_shots *= difficulty.fire_cooldown;
+                    *aim = event.aim;
+                }
+                _ => {}
+            }
+        });
+
+    if let Some(boss) = &event.boss {
+        world.insert(new_enemy, Boss::new(bosses.get(boss)));
+        // Bosses fly in from off screen, they are only culled once they escape
+        world.insert(new_enemy, NoCull);
+    }
+
+    if let Some(path) = &event.path {
+        world.insert(new_enemy, Path::new(path.clone(), spawn.position));
+    }
+
+    if let Some(formation) = formation {
+        world.insert(new_enemy, FormationMember::new(formation, spawn.member));
+    }
+
+    if let Some(behavior) = &event.behavior {
+        world.insert(new_enemy, Behavior::new(behaviors.get(behavior)));
+    }
+}
+
+pub fn on_enemy_ded(
+    position: Vec2,
+    prefabs: &Prefabs,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) {
+    prefabs.spawn(
+        "explosion",
+        Vec2::randomize(position, 10.0),
+        entities,
+        world,
     );
-    world.insert(explosion, crate::ecs::animation::Animation::new(30, 4, 4));
-    world.insert(explosion, Lifetime { time_left: 0.50 });
+
+    crate::ecs::pickup::maybe_drop_power(position, prefabs, entities, world);
 }
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Where a character is on the font's spritesheet
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Glyph {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// How far the pen moves after drawing this glyph
+    pub advance: u32,
+}
+
+/// A font drawn from a spritesheet, with glyph metrics from a data file
+#[derive(Debug, Deserialize)]
+pub struct BitmapFont {
+    /// Path of the spritesheet, as listed in `SPRITE_PATHS`
+    pub sprite: String,
+    #[serde(skip)]
+    pub spritesheet: usize,
+    /// Distance between the tops of two lines of text
+    pub line_height: u32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl BitmapFont {
+    pub fn load(path: &str) -> Result<BitmapFont, String> {
+        let mut font: BitmapFont = crate::assets::load_ron(path)?;
+        font.spritesheet = crate::assets::sprite_id(&font.sprite)
+            .ok_or_else(|| format!("{}: unknown sprite {}", path, font.sprite))?;
+        Ok(font)
+    }
+
+    /// The glyph for `character`, falling back to its uppercase glyph.
+    /// Characters the font has no glyph for are skipped when drawing.
+    pub fn glyph(&self, character: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&character.to_ascii_uppercase()))
+    }
+
+    /// Width and height of `text` in pixels at a scale of 1
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        let mut width = 0;
+        let mut line_width = 0;
+        let mut lines = 1;
+        for character in text.chars() {
+            if character == '\n' {
+                width = width.max(line_width);
+                line_width = 0;
+                lines += 1;
+            } else if let Some(glyph) = self.glyph(character) {
+                line_width += glyph.advance;
+            }
+        }
+        (width.max(line_width), lines * self.line_height)
+    }
+}
@@ -0,0 +1,281 @@
+use rand::Rng;
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use crate::ecs::components::*;
+use crate::ecs::path::*;
+use crate::ecs::player::*;
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// Top speed of members catching up with their slot
+const FORMATION_RETURN_SPEED: f32 = 400.0;
+/// How hard members are pulled towards their slot, per pixel of distance
+const FORMATION_SLOT_PULL: f32 = 4.0;
+
+/// Members leaving the formation to fly at the player
+#[derive(Clone, Debug, Deserialize)]
+pub struct DiveConfig {
+    /// Seconds between dives
+    pub interval: f32,
+    pub speed: f32,
+    /// Seconds a dive lasts before the member heads back to its slot
+    pub duration: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum FormationAction {
+    /// Scales the space between slots
+    Tighten(f32),
+    /// The whole formation flies off the top of the screen at this speed
+    Flee(f32),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FormationReaction {
+    /// Fraction of members still alive below which the action is taken
+    pub below: f32,
+    pub action: FormationAction,
+}
+
+/// A formation as written in stage files. Members fill a grid of slots
+/// centered on the anchor, row by row.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FormationConfig {
+    pub columns: u32,
+    /// Space between neighbouring slots
+    pub spacing: Vec2,
+    /// Path the anchor follows, relative to the middle of where the members
+    /// spawn
+    #[serde(default)]
+    pub path: Option<PathConfig>,
+    #[serde(default)]
+    pub dive: Option<DiveConfig>,
+    /// Taken once each as members die, in order
+    #[serde(default)]
+    pub reactions: Vec<FormationReaction>,
+}
+
+/// Offset from the anchor of a slot in a grid `columns` slots wide
+fn slot_offset(columns: u32, spacing: Vec2, slot: u32) -> Vec2 {
+    let columns = columns.max(1);
+    let column = (slot % columns) as f32 - (columns - 1) as f32 / 2.0;
+    let row = (slot / columns) as f32;
+    Vec2 {
+        x: column * spacing.x,
+        y: row * spacing.y,
+    }
+}
+
+/// The anchor of a group of enemies
+pub struct Formation {
+    config: FormationConfig,
+    /// Most members alive at once, losses are counted against this
+    size: usize,
+    spacing_scale: f32,
+    dive_cooldown: f32,
+    reactions_taken: usize,
+    fleeing: Option<f32>,
+}
+
+impl Component for Formation {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Formation {
+    pub fn new(config: FormationConfig) -> Formation {
+        Formation {
+            dive_cooldown: config.dive.as_ref().map_or(0.0, |dive| dive.interval),
+            config,
+            size: 0,
+            spacing_scale: 1.0,
+            reactions_taken: 0,
+            fleeing: None,
+        }
+    }
+}
+
+enum MemberState {
+    InSlot,
+    Diving { direction: Vec2, time_left: f32 },
+}
+
+pub struct FormationMember {
+    pub formation: Entity,
+    slot: u32,
+    state: MemberState,
+}
+
+impl Component for FormationMember {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl FormationMember {
+    pub fn new(formation: Entity, slot: u32) -> FormationMember {
+        FormationMember {
+            formation,
+            slot,
+            state: MemberState::InSlot,
+        }
+    }
+}
+
+/// What members need to know about their anchor this frame
+struct AnchorState {
+    position: Vec2,
+    velocity: Vec2,
+    columns: u32,
+    spacing: Vec2,
+    dive_speed: f32,
+    dive_duration: f32,
+}
+
+/// Moves formation anchors, keeps members in their slots and sends them
+/// on dives
+pub struct FormationSystem;
+
+impl<'a> System<'a> for FormationSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Formation>,
+        WriteStorage<'a, FormationMember>,
+        WriteStorage<'a, Path>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            delta_time,
+            position_storage,
+            player_storage,
+            mut velocity_storage,
+            mut formation_storage,
+            mut member_storage,
+            mut path_storage,
+        ) = data;
+        let delta_time = delta_time.0;
+
+        let player_position = (&position_storage, &player_storage)
+            .join()
+            .next()
+            .map(|(position, _)| position.position);
+
+        let mut members: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        for (entity, member) in (&entities, &member_storage).join() {
+            members.entry(member.formation).or_default().push(entity);
+        }
+
+        let mut anchors = HashMap::new();
+        let mut divers = Vec::new();
+        for (anchor, formation, position, velocity) in (
+            &entities,
+            &mut formation_storage,
+            &position_storage,
+            &mut velocity_storage,
+        )
+            .join()
+        {
+            let alive = members.get(&anchor).map_or(&[][..], Vec::as_slice);
+            if alive.is_empty() {
+                entities.delete(anchor).expect("error deleting formation");
+                continue;
+            }
+            formation.size = formation.size.max(alive.len());
+
+            let remaining = alive.len() as f32 / formation.size as f32;
+            while let Some(reaction) = formation.config.reactions.get(formation.reactions_taken) {
+                if remaining >= reaction.below {
+                    break;
+                }
+                match reaction.action {
+                    FormationAction::Tighten(scale) => formation.spacing_scale *= scale,
+                    FormationAction::Flee(speed) => {
+                        formation.fleeing = Some(speed);
+                        path_storage.remove(anchor);
+                    }
+                }
+                formation.reactions_taken += 1;
+            }
+
+            if let Some(speed) = formation.fleeing {
+                velocity.velocity = Vec2::up() * speed;
+            } else if let Some(dive) = &formation.config.dive {
+                formation.dive_cooldown -= delta_time;
+                if formation.dive_cooldown <= 0.0 {
+                    formation.dive_cooldown = dive.interval;
+                    divers.push(alive[rand::thread_rng().gen_range(0, alive.len())]);
+                }
+            }
+
+            let (dive_speed, dive_duration) = formation
+                .config
+                .dive
+                .as_ref()
+                .map_or((0.0, 0.0), |dive| (dive.speed, dive.duration));
+            anchors.insert(
+                anchor,
+                AnchorState {
+                    position: position.position,
+                    velocity: velocity.velocity,
+                    columns: formation.config.columns,
+                    spacing: formation.config.spacing * formation.spacing_scale,
+                    dive_speed,
+                    dive_duration,
+                },
+            );
+        }
+
+        for (entity, member, position, velocity) in (
+            &entities,
+            &mut member_storage,
+            &position_storage,
+            &mut velocity_storage,
+        )
+            .join()
+        {
+            // Members that lost their anchor keep drifting the way they were going
+            let anchor = match anchors.get(&member.formation) {
+                Some(anchor) => anchor,
+                None => continue,
+            };
+
+            if let (MemberState::InSlot, Some(player)) = (&member.state, player_position) {
+                let offset = player - position.position;
+                if divers.contains(&entity) && offset.squared_length() > 1.0 {
+                    member.state = MemberState::Diving {
+                        direction: offset.normalzed(),
+                        time_left: anchor.dive_duration,
+                    };
+                }
+            }
+
+            match &mut member.state {
+                MemberState::Diving {
+                    direction,
+                    time_left,
+                } => {
+                    velocity.velocity = *direction * anchor.dive_speed;
+                    *time_left -= delta_time;
+                    if *time_left <= 0.0 {
+                        member.state = MemberState::InSlot;
+                    }
+                }
+                MemberState::InSlot => {
+                    let slot =
+                        anchor.position + slot_offset(anchor.columns, anchor.spacing, member.slot);
+                    let mut pull = (slot - position.position) * FORMATION_SLOT_PULL;
+                    if pull.length() > FORMATION_RETURN_SPEED {
+                        pull = pull.normalzed() * FORMATION_RETURN_SPEED;
+                    }
+                    velocity.velocity = anchor.velocity + pull;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use specs::prelude::*;
+
+use crate::ecs::difficulty::Difficulty;
+use crate::ecs::rank::Rank;
+use crate::ecs::resources::*;
+use crate::ecs::score::Score;
+use crate::vec2::Vec2;
+
+/// How much of the previous FPS reading is kept each frame, so the counter
+/// does not flicker
+const FPS_SMOOTHING: f32 = 0.95;
+
+/// Corner of the screen a HUD element is placed against
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// What a HUD element shows
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum HudField {
+    Score,
+    Chain,
+    Lives,
+    Bombs,
+    Power,
+    Rank,
+    Difficulty,
+    Fps,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HudElement {
+    pub field: HudField,
+    /// Written before the value
+    #[serde(default)]
+    pub label: String,
+    pub anchor: Anchor,
+    /// Distance from the anchored corner, towards the middle of the screen
+    pub offset: Vec2,
+    /// Screen pixels per font pixel
+    pub scale: u32,
+    /// Red, green and blue of the text
+    pub color: (u8, u8, u8),
+    /// Filled in every frame by the `HudSystem`
+    #[serde(skip)]
+    pub text: String,
+}
+
+/// Text drawn over the playfield, loaded from a data file
+#[derive(Default)]
+pub struct Hud {
+    pub elements: Vec<HudElement>,
+}
+
+impl Hud {
+    pub fn load(path: &str) -> Result<Hud, String> {
+        let elements = crate::assets::load_ron(path)?;
+        Ok(Hud { elements })
+    }
+}
+
+/// Updates the text of every HUD element from the game state
+#[derive(Default)]
+pub struct HudSystem {
+    fps: f32,
+}
+
+impl<'a> System<'a> for HudSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        Read<'a, Score>,
+        Read<'a, PlayerStats>,
+        Read<'a, Rank>,
+        ReadExpect<'a, Difficulty>,
+        Write<'a, Hud>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, score, stats, rank, difficulty, mut hud) = data;
+
+        if delta_time.0 > 0.0 {
+            self.fps = self.fps * FPS_SMOOTHING + (1.0 - FPS_SMOOTHING) / delta_time.0;
+        }
+
+        for element in hud.elements.iter_mut() {
+            let value = match element.field {
+                HudField::Score => score.score.to_string(),
+                HudField::Chain => format!("{} x{:.1}", score.chain, score.multiplier()),
+                HudField::Lives => stats.lives.to_string(),
+                HudField::Bombs => stats.bombs.to_string(),
+                HudField::Power => stats.power.to_string(),
+                HudField::Rank => format!("{:.0}%", rank.value * 100.0),
+                HudField::Difficulty => difficulty.name.clone(),
+                HudField::Fps => format!("{:.0}", self.fps),
+            };
+            element.text = format!("{}{}", element.label, value);
+        }
+    }
+}
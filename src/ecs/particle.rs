@@ -0,0 +1,205 @@
+use rand::Rng;
+use serde::Deserialize;
+use specs::prelude::*;
+use specs_derive::Component;
+
+use crate::ecs::components::*;
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// Most particles alive at once, emitters stop emitting while it is reached
+const MAX_PARTICLES: usize = 8192;
+/// Particles change color in this many steps over their life, so they share
+/// colors and are drawn in fewer batches
+const COLOR_STEPS: f32 = 16.0;
+
+/// Red, green, blue and alpha
+type Rgba = (u8, u8, u8, u8);
+
+fn full_circle() -> f32 {
+    360.0
+}
+
+/// How particles look and move, shared by every particle of an emitter
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct ParticleStyle {
+    /// Added to the velocity every second
+    #[serde(default)]
+    pub gravity: Vec2,
+    /// Color at the start and at the end of a life
+    pub color: (Rgba, Rgba),
+    /// Width and height in pixels at the start and at the end of a life
+    pub size: (f32, f32),
+    #[serde(default = "crate::assets::blend")]
+    pub blend: SpriteBlend,
+    /// Particles are drawn in front of the sprites of their layer
+    pub layer: RenderLayer,
+}
+
+/// A square of color that moves on its own until it dies. Particles are not
+/// entities, they are kept and updated together in `Particles`.
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    pub style: ParticleStyle,
+}
+
+impl Particle {
+    /// Between 0 when the particle is emitted and 1 when it dies
+    fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+
+    pub fn color(&self) -> sdl2::pixels::Color {
+        let life = (self.life() * COLOR_STEPS).floor() / COLOR_STEPS;
+        let (start, end) = self.style.color;
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * life) as u8;
+        sdl2::pixels::Color::RGBA(
+            mix(start.0, end.0),
+            mix(start.1, end.1),
+            mix(start.2, end.2),
+            mix(start.3, end.3),
+        )
+    }
+
+    pub fn size(&self) -> f32 {
+        let (start, end) = self.style.size;
+        start + (end - start) * self.life()
+    }
+}
+
+/// Every live particle
+#[derive(Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+}
+
+impl Particles {
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+
+    fn emit(&mut self, particle: Particle) {
+        if self.particles.len() < MAX_PARTICLES {
+            self.particles.push(particle);
+        }
+    }
+}
+
+/// Emits particles from its entity's position, in a cone around `direction`
+#[derive(Component, Clone, Debug, Deserialize)]
+#[storage(HashMapStorage)]
+pub struct ParticleEmitter {
+    /// Particles per second
+    #[serde(default)]
+    pub rate: f32,
+    /// Particles emitted at once as soon as the emitter starts
+    #[serde(default)]
+    pub burst: u32,
+    /// Seconds `rate` particles are emitted for, forever if not given
+    #[serde(default)]
+    pub duration: Option<f32>,
+    /// Shortest and longest life of a particle in seconds
+    pub lifetime: (f32, f32),
+    /// Slowest and fastest starting speed in pixels per second
+    pub speed: (f32, f32),
+    /// Degrees clockwise from up
+    #[serde(default)]
+    pub direction: f32,
+    /// Width of the cone in degrees
+    #[serde(default = "full_circle")]
+    pub spread: f32,
+    pub style: ParticleStyle,
+    #[serde(skip)]
+    elapsed: f32,
+    /// Particles owed from earlier frames where `rate` added up to a fraction
+    #[serde(skip)]
+    pending: f32,
+    #[serde(skip)]
+    burst_done: bool,
+}
+
+impl ParticleEmitter {
+    fn particle<R: Rng>(&self, position: Vec2, rng: &mut R) -> Particle {
+        let angle = (self.direction + rng.gen_range(-0.5, 0.5) * self.spread).to_radians();
+        let speed = random_between(rng, self.speed);
+        Particle {
+            position,
+            velocity: Vec2 {
+                x: angle.sin() * speed,
+                y: -angle.cos() * speed,
+            },
+            age: 0.0,
+            lifetime: random_between(rng, self.lifetime).max(f32::EPSILON),
+            style: self.style,
+        }
+    }
+}
+
+fn random_between<R: Rng>(rng: &mut R, (min, max): (f32, f32)) -> f32 {
+    if max > min {
+        rng.gen_range(min, max)
+    } else {
+        min
+    }
+}
+
+/// Moves and ages every particle and emits new ones from emitters
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, ParticleEmitter>,
+        Write<'a, Particles>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, position_storage, mut emitter_storage, mut particles) = data;
+        let delta_time = delta_time.0;
+
+        // Order does not matter when drawing, so dead particles are swapped out
+        // instead of shifting everything after them
+        let mut i = 0;
+        while i < particles.particles.len() {
+            let particle = &mut particles.particles[i];
+            particle.age += delta_time;
+            if particle.age >= particle.lifetime {
+                particles.particles.swap_remove(i);
+                continue;
+            }
+            particle.velocity += particle.style.gravity * delta_time;
+            let velocity = particle.velocity;
+            particle.position += velocity * delta_time;
+            i += 1;
+        }
+
+        let mut rng = rand::thread_rng();
+        for (pos, emitter) in (&position_storage, &mut emitter_storage).join() {
+            let mut count = 0;
+            if !emitter.burst_done {
+                count += emitter.burst;
+                emitter.burst_done = true;
+            }
+            let emitting = match emitter.duration {
+                Some(duration) => emitter.elapsed < duration,
+                None => true,
+            };
+            if emitting {
+                emitter.pending += emitter.rate * delta_time;
+                let whole = emitter.pending.floor();
+                emitter.pending -= whole;
+                count += whole as u32;
+            }
+            emitter.elapsed += delta_time;
+
+            for _ in 0..count {
+                particles.emit(emitter.particle(pos.position, &mut rng));
+            }
+        }
+    }
+}
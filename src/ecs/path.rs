@@ -0,0 +1,308 @@
+use serde::Deserialize;
+use specs::prelude::*;
+
+use crate::ecs::components::*;
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// Longest a path whose shape does not start at its origin takes to glide onto
+/// the shape, instead of jumping there in a single frame
+const ENTRY_DURATION: f32 = 1.0;
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum PathShape {
+    /// Straight lines from point to point
+    Linear(Vec<Vec2>),
+    /// A smooth curve passing through every point
+    CatmullRom(Vec<Vec2>),
+    /// Cubic bezier segments laid out as start, control, control, end, where
+    /// each end is the start of the next segment
+    Bezier(Vec<Vec2>),
+}
+
+impl PathShape {
+    /// Point on the shape at `t` between 0 and 1
+    fn sample(&self, t: f32) -> Vec2 {
+        match self {
+            PathShape::Linear(points) => {
+                let (i, local) = segment(t, points.len().saturating_sub(1));
+                match (points.get(i), points.get(i + 1)) {
+                    (Some(&a), Some(&b)) => a + (b - a) * local,
+                    (Some(&a), None) => a,
+                    _ => Vec2::default(),
+                }
+            }
+            PathShape::CatmullRom(points) => {
+                if points.len() < 2 {
+                    return points.first().copied().unwrap_or_default();
+                }
+                let (i, local) = segment(t, points.len() - 1);
+                let at = |index: isize| {
+                    let clamped = index.clamp(0, points.len() as isize - 1);
+                    points[clamped as usize]
+                };
+                let i = i as isize;
+                catmull_rom(at(i - 1), at(i), at(i + 1), at(i + 2), local)
+            }
+            PathShape::Bezier(points) => {
+                let segments = points.len().saturating_sub(1) / 3;
+                if segments == 0 {
+                    return points.first().copied().unwrap_or_default();
+                }
+                let (i, local) = segment(t, segments);
+                let p = &points[i * 3..i * 3 + 4];
+                cubic_bezier(p[0], p[1], p[2], p[3], local)
+            }
+        }
+    }
+}
+
+/// Splits `t` over `count` equal segments into a segment index and the
+/// position within that segment
+fn segment(t: f32, count: usize) -> (usize, f32) {
+    if count == 0 {
+        return (0, 0.0);
+    }
+    let scaled = t.clamp(0.0, 1.0) * count as f32;
+    let index = (scaled as usize).min(count - 1);
+    (index, scaled - index as f32)
+}
+
+fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+fn cubic_bezier(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+/// What happens when the end of the path is reached
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum PathMode {
+    #[default]
+    Once,
+    Loop,
+    PingPong,
+}
+
+/// How progress along the path speeds up and slows down over one pass
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum SpeedCurve {
+    #[default]
+    Constant,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl SpeedCurve {
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            SpeedCurve::Constant => t,
+            SpeedCurve::EaseIn => t * t,
+            SpeedCurve::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            SpeedCurve::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Sideways wobble on top of the path
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SineOffset {
+    pub amplitude: f32,
+    /// Full waves per second
+    pub frequency: f32,
+}
+
+/// A path as written in data files, points are relative to where it starts.
+/// A shape that does not start at (0, 0) is glided onto from the start.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PathConfig {
+    pub shape: PathShape,
+    /// Seconds one pass over the path takes
+    pub duration: f32,
+    #[serde(default)]
+    pub speed_curve: SpeedCurve,
+    #[serde(default)]
+    pub mode: PathMode,
+    #[serde(default)]
+    pub sine: Option<SineOffset>,
+}
+
+pub struct Path {
+    config: PathConfig,
+    origin: Vec2,
+    /// Where the shape starts relative to `origin`
+    entry: Vec2,
+    elapsed: f32,
+    pub finished: bool,
+}
+
+impl Component for Path {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Path {
+    pub fn new(config: PathConfig, origin: Vec2) -> Path {
+        Path {
+            entry: config.shape.sample(0.0),
+            config,
+            origin,
+            elapsed: 0.0,
+            finished: false,
+        }
+    }
+
+    /// Progress along the shape after applying the mode and speed curve
+    fn progress(&self) -> f32 {
+        let passes = self.elapsed / self.config.duration.max(f32::EPSILON);
+        let t = match self.config.mode {
+            PathMode::Once => passes.min(1.0),
+            PathMode::Loop => passes.fract(),
+            PathMode::PingPong => {
+                if passes % 2.0 < 1.0 {
+                    passes.fract()
+                } else {
+                    1.0 - passes.fract()
+                }
+            }
+        };
+        self.config.speed_curve.apply(t)
+    }
+
+    fn target(&self) -> Vec2 {
+        let t = self.progress();
+        let mut target = self.origin + self.config.shape.sample(t);
+
+        // Paths shorter than the glide finish it as they end, so they still
+        // stop at the end of the shape
+        let entry_duration = ENTRY_DURATION.min(self.config.duration);
+        if self.elapsed < entry_duration {
+            let entered = SpeedCurve::EaseInOut.apply(self.elapsed / entry_duration);
+            target = target - self.entry * (1.0 - entered);
+        }
+
+        if let Some(sine) = self.config.sine {
+            let ahead = self.config.shape.sample((t + 0.01).min(1.0));
+            let behind = self.config.shape.sample((t - 0.01).max(0.0));
+            let tangent = ahead - behind;
+            if tangent.squared_length() > 0.0 {
+                let normal = tangent.normalzed().rotated(std::f32::consts::FRAC_PI_2);
+                let wave = (self.elapsed * sine.frequency * std::f32::consts::PI * 2.0).sin();
+                target += normal * (wave * sine.amplitude);
+            }
+        }
+        target
+    }
+}
+
+/// Steers entities along their path by setting their velocity
+pub struct PathSystem;
+
+impl<'a> System<'a> for PathSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Path>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, position_storage, mut velocity_storage, mut path_storage) = data;
+        let delta_time = delta_time.0;
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        for (position, velocity, path) in
+            (&position_storage, &mut velocity_storage, &mut path_storage).join()
+        {
+            if path.finished {
+                continue;
+            }
+
+            if let PathMode::Once = path.config.mode {
+                if path.elapsed >= path.config.duration {
+                    path.finished = true;
+                    velocity.velocity = Vec2::default();
+                    continue;
+                }
+            }
+
+            path.elapsed += delta_time;
+            let target = path.target();
+            velocity.velocity = (target - position.position) * (1.0 / delta_time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(Vec2::distance(a, b) < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn linear_moves_evenly_over_segments() {
+        let shape = PathShape::Linear(vec![v(0.0, 0.0), v(10.0, 0.0), v(10.0, 10.0)]);
+        assert_close(shape.sample(0.0), v(0.0, 0.0));
+        assert_close(shape.sample(0.25), v(5.0, 0.0));
+        assert_close(shape.sample(0.5), v(10.0, 0.0));
+        assert_close(shape.sample(0.75), v(10.0, 5.0));
+        assert_close(shape.sample(1.0), v(10.0, 10.0));
+        // Out of range progress stays on the ends
+        assert_close(shape.sample(2.0), v(10.0, 10.0));
+    }
+
+    #[test]
+    fn bezier_starts_and_ends_on_its_end_points() {
+        let shape = PathShape::Bezier(vec![
+            v(0.0, 0.0),
+            v(0.0, 10.0),
+            v(10.0, 10.0),
+            v(10.0, 0.0),
+            v(10.0, -10.0),
+            v(20.0, -10.0),
+            v(20.0, 0.0),
+        ]);
+        assert_close(shape.sample(0.0), v(0.0, 0.0));
+        assert_close(shape.sample(0.25), v(5.0, 7.5));
+        assert_close(shape.sample(0.5), v(10.0, 0.0));
+        assert_close(shape.sample(1.0), v(20.0, 0.0));
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_point() {
+        let points = vec![v(0.0, 0.0), v(10.0, 5.0), v(20.0, -5.0), v(30.0, 0.0)];
+        let shape = PathShape::CatmullRom(points.clone());
+        for (i, point) in points.iter().enumerate() {
+            assert_close(shape.sample(i as f32 / 3.0), *point);
+        }
+    }
+
+    #[test]
+    fn short_shapes_do_not_panic() {
+        assert_close(PathShape::Linear(vec![]).sample(0.5), Vec2::default());
+        assert_close(
+            PathShape::CatmullRom(vec![v(1.0, 2.0)]).sample(0.5),
+            v(1.0, 2.0),
+        );
+        assert_close(
+            PathShape::Bezier(vec![v(1.0, 2.0), v(3.0, 4.0)]).sample(0.5),
+            v(1.0, 2.0),
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use rand::Rng;
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+
+use crate::ecs::components::*;
+use crate::ecs::player::*;
+use crate::ecs::prefab::Prefabs;
+use crate::ecs::resources::*;
+use crate::ecs::weapon::*;
+use crate::vec2::Vec2;
+
+/// Chance that a dying enemy leaves a power pickup behind
+pub const POWER_DROP_CHANCE: f64 = 0.15;
+/// Points for collecting power when the weapon is already at full power
+pub const MAX_POWER_PICKUP_BONUS: u32 = 1000;
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum PickupKind {
+    Power(u32),
+}
+
+#[derive(Debug)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub radius: f32,
+}
+
+impl Component for Pickup {
+    type Storage = HashMapStorage<Self>;
+}
+
+pub struct PickupSystem;
+
+impl<'a> System<'a> for PickupSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Pickup>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, Weapon>,
+        Write<'a, PlayerStats>,
+        Write<'a, EventChannel<GameEvent>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            position_storage,
+            pickup_storage,
+            player_storage,
+            mut weapon_storage,
+            mut stats,
+            mut events,
+        ) = data;
+
+        for (player_pos, _, weapon) in
+            (&position_storage, &player_storage, &mut weapon_storage).join()
+        {
+            for (entity, pickup_pos, pickup) in
+                (&entities, &position_storage, &pickup_storage).join()
+            {
+                if Vec2::distance(player_pos.position, pickup_pos.position) > pickup.radius {
+                    continue;
+                }
+
+                match pickup.kind {
+                    PickupKind::Power(_) if stats.power >= MAX_POWER => {
+                        events.single_write(GameEvent::Bonus(
+                            pickup_pos.position,
+                            MAX_POWER_PICKUP_BONUS,
+                        ));
+                    }
+                    PickupKind::Power(amount) => {
+                        stats.power = (stats.power + amount).min(MAX_POWER);
+                        weapon.apply_power(stats.power, &PLAYER_POWER_TIERS);
+                    }
+                }
+
+                entities
+                    .delete(entity)
+                    .expect("error deleting collected pickup");
+            }
+        }
+    }
+}
+
+pub fn maybe_drop_power(
+    position: Vec2,
+    prefabs: &Prefabs,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) {
+    if !rand::thread_rng().gen_bool(POWER_DROP_CHANCE) {
+        return;
+    }
+
+    prefabs.spawn("power_pickup", position, entities, world);
+}
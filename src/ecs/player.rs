@@ -1,5 +1,9 @@
-use crate::ecs::collision::*;
 use crate::ecs::components::*;
+use crate::ecs::difficulty::*;
+use crate::ecs::prefab::*;
+use crate::ecs::resources::*;
+use crate::ecs::score::Score;
+use crate::ecs::weapon::*;
 use specs::prelude::*;
 
 pub struct Player {}
@@ -23,13 +27,24 @@ impl<'a> System<'a> for PlayerSystem {
     fn run(&mut self, data: Self::SystemData) {}
 }
 
-pub struct PlayerRespawnSystem;
+#[derive(Default)]
+pub struct PlayerRespawnSystem {
+    has_spawned: bool,
+}
 
 impl<'a> System<'a> for PlayerRespawnSystem {
-    type SystemData = (Entities<'a>, ReadStorage<'a, Player>, Read<'a, LazyUpdate>);
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Player>,
+        ReadExpect<'a, Difficulty>,
+        Read<'a, Prefabs>,
+        Write<'a, PlayerStats>,
+        Write<'a, Score>,
+        Read<'a, LazyUpdate>,
+    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, player_storage, world) = data;
+        let (entities, player_storage, difficulty, prefabs, mut stats, mut score, world) = data;
 
         let mut player_alive = false;
 
@@ -38,63 +53,48 @@ impl<'a> System<'a> for PlayerRespawnSystem {
         }
 
         if !player_alive {
-            let new_player = entities.create();
-
-            world.insert(new_player, Position::new(800.0, 800.0));
-
-            world.insert(
-                new_player,
-                Sprite {
-                    spritesheet: crate::assets::PLAYER_SPRITE_ID,
-                    size: sdl2::rect::Point::new(64, 64),
-                    src_rect: sdl2::rect::Rect::new(0, 0, 128, 128),
-                },
-            );
-
-            world.insert(new_player, Velocity::default());
-
-            world.insert(
-                new_player,
-                CircleCollider {
-                    radius: 22.0,
-                    layer: LayerMask::from_enum(Layers::Player),
-                    collides_with: LayerMask::from_enum(Layers::Enemy),
-                },
+            if self.has_spawned {
+                stats.power = stats.power.saturating_sub(POWER_LOST_ON_DEATH);
+                stats.lives = stats.lives.saturating_sub(1);
+            }
+            if !self.has_spawned || stats.lives == 0 {
+                if self.has_spawned {
+                    *score = Score::default();
+                }
+                stats.lives = difficulty.lives;
+                stats.power = 0;
+            }
+            stats.bombs = difficulty.bombs;
+            self.has_spawned = true;
+
+            let new_player = prefabs.spawn(
+                "player",
+                crate::vec2::Vec2 { x: 800.0, y: 800.0 },
+                &entities,
+                &world,
             );
 
-            world.insert(new_player, Health::new(25, on_player_ded));
-            world.insert(new_player, Damage::new(5));
-            world.insert(new_player, Player::new());
-            world.insert(new_player, KeyboardControlled {});
-
-            world.insert(
-                new_player,
-                crate::ecs::weapon::Weapon::new(1400.0, 0.015, 25),
-            );
+            // Runs after the prefab's components are in, so the weapon exists
+            let power = stats.power;
+            world.exec(move |world| {
+                if let Some(weapon) = world.write_storage::<Weapon>().get_mut(new_player) {
+                    weapon.apply_power(power, &PLAYER_POWER_TIERS);
+                }
+            });
         }
     }
 }
 
-fn on_player_ded(
+pub fn on_player_ded(
     position: crate::vec2::Vec2,
+    prefabs: &Prefabs,
     entities: &specs::Entities,
     world: &specs::LazyUpdate,
 ) {
-    let explosion = entities.create();
-    world.insert(
-        explosion,
-        Position {
-            position: crate::vec2::Vec2::randomize(position, 10.0),
-        },
-    );
-    world.insert(
-        explosion,
-        Sprite {
-            spritesheet: crate::assets::EXPLOSION_SPRITE_ID,
-            size: sdl2::rect::Point::new(128, 128),
-            src_rect: sdl2::rect::Rect::new(0, 0, 64, 64),
-        },
+    prefabs.spawn(
+        "big_explosion",
+        crate::vec2::Vec2::randomize(position, 10.0),
+        entities,
+        world,
     );
-    world.insert(explosion, crate::ecs::animation::Animation::new(30, 4, 4));
-    world.insert(explosion, Lifetime { time_left: 0.50 });
 }
@@ -0,0 +1,261 @@
+use specs::prelude::*;
+use specs_derive::Component;
+
+use crate::ecs::collision::*;
+use crate::ecs::components::*;
+
+/// Marks entities owned by the `ProjectilePool`, they are handed back to the
+/// pool instead of being deleted
+#[derive(Component, Debug, Default)]
+pub struct Pooled {
+    /// Times the entity has been reused, so a projectile that is remembered by
+    /// entity can be told apart from later ones fired with the same entity
+    pub generation: u32,
+}
+
+/// The components a projectile is (re)initialized with
+pub struct ProjectileSpawn {
+    pub position: Position,
+    pub velocity: Velocity,
+    pub sprite: Sprite,
+    pub collider: CircleCollider,
+    pub health: Health,
+    pub damage: Damage,
+}
+
+/// Recycles projectile entities so firing does not create and delete an entity
+/// with a full set of components for every shot.
+#[derive(Default)]
+pub struct ProjectilePool {
+    free: Vec<Entity>,
+    released: Vec<Entity>,
+    requested: Vec<ProjectileSpawn>,
+}
+
+impl ProjectilePool {
+    /// Queues a projectile to be spawned by the `ProjectilePoolSystem` this frame
+    pub fn request(&mut self, spawn: ProjectileSpawn) {
+        self.requested.push(spawn);
+    }
+
+    /// Queues a pooled entity to be deactivated and returned to the free list
+    pub fn release(&mut self, entity: Entity) {
+        self.released.push(entity);
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.free.len()
+    }
+}
+
+/// Deletes `entity`, or releases it to the pool if it is a pooled projectile
+pub fn despawn(
+    entity: Entity,
+    entities: &Entities,
+    pooled_storage: &ReadStorage<Pooled>,
+    pool: &mut ProjectilePool,
+) {
+    if pooled_storage.contains(entity) {
+        pool.release(entity);
+    } else {
+        entities.delete(entity).expect("error deleting entity");
+    }
+}
+
+/// Despawns every projectile fired by enemies, for boss phase changes
+pub fn clear_enemy_bullets(
+    entities: &Entities,
+    projectile_storage: &ReadStorage<Projectile>,
+    collider_storage: &ReadStorage<CircleCollider>,
+    pooled_storage: &ReadStorage<Pooled>,
+    pool: &mut ProjectilePool,
+) {
+    let enemy_layer = LayerMask::from_enum(Layers::Enemy);
+    for (entity, _, collider) in (entities, projectile_storage, collider_storage).join() {
+        if collider.layer.any(&enemy_layer) {
+            despawn(entity, entities, pooled_storage, pool);
+        }
+    }
+}
+
+pub struct ProjectilePoolSystem;
+
+impl<'a> System<'a> for ProjectilePoolSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, ProjectilePool>,
+        WriteStorage<'a, Pooled>,
+        WriteStorage<'a, Projectile>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Sprite>,
+        WriteStorage<'a, CircleCollider>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Damage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut pool,
+            mut pooled_storage,
+            mut projectile_storage,
+            mut position_storage,
+            mut velocity_storage,
+            mut sprite_storage,
+            mut collider_storage,
+            mut health_storage,
+            mut damage_storage,
+        ) = data;
+        let pool = &mut *pool;
+
+        // Removing the position takes a projectile out of every system that
+        // moves, draws or collides it. An entity can be released more than once
+        // per frame, only the first release puts it back on the free list.
+        for entity in pool.released.drain(..) {
+            if position_storage.remove(entity).is_some() {
+                pool.free.push(entity);
+            }
+        }
+
+        for spawn in pool.requested.drain(..) {
+            let projectile = match pool.free.pop() {
+                Some(entity) => entity,
+                None => entities.create(),
+            };
+
+            let insert_failed = "could not insert component on pooled projectile";
+            let generation = pooled_storage
+                .get(projectile)
+                .map_or(0, |pooled| pooled.generation.wrapping_add(1));
+            pooled_storage
+                .insert(projectile, Pooled { generation })
+                .expect(insert_failed);
+            projectile_storage
+                .insert(projectile, Projectile {})
+                .expect(insert_failed);
+            position_storage
+                .insert(projectile, spawn.position)
+                .expect(insert_failed);
+            velocity_storage
+                .insert(projectile, spawn.velocity)
+                .expect(insert_failed);
+            sprite_storage
+                .insert(projectile, spawn.sprite)
+                .expect(insert_failed);
+            collider_storage
+                .insert(projectile, spawn.collider)
+                .expect(insert_failed);
+            health_storage
+                .insert(projectile, spawn.health)
+                .expect(insert_failed);
+            damage_storage
+                .insert(projectile, spawn.damage)
+                .expect(insert_failed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn() -> ProjectileSpawn {
+        ProjectileSpawn {
+            position: Position::new(10.0, 20.0),
+            velocity: Velocity::new(0.0, -100.0),
+            sprite: Sprite::new(
+                0,
+                sdl2::rect::Rect::new(0, 0, 16, 16),
+                sdl2::rect::Point::new(16, 16),
+                RenderLayer::Bullets,
+            ),
+            collider: CircleCollider {
+                radius: 8.0,
+                layer: LayerMask::from_enum(Layers::Enemy),
+                collides_with: LayerMask::from_enum(Layers::Player),
+            },
+            health: Health::new(1, |_, _, _, _| {}),
+            damage: Damage::new(1),
+        }
+    }
+
+    fn active(world: &World) -> Vec<Entity> {
+        let entities = world.entities();
+        let pooled_storage = world.read_storage::<Pooled>();
+        let position_storage = world.read_storage::<Position>();
+        (&entities, &pooled_storage, &position_storage)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
+
+    fn run(world: &mut World) {
+        ProjectilePoolSystem.run_now(world);
+        world.maintain();
+    }
+
+    fn setup() -> World {
+        let mut world = World::new();
+        System::setup(&mut ProjectilePoolSystem, &mut world);
+        world
+    }
+
+    #[test]
+    fn released_projectiles_are_reused() {
+        let mut world = setup();
+        world.write_resource::<ProjectilePool>().request(spawn());
+        world.write_resource::<ProjectilePool>().request(spawn());
+        run(&mut world);
+        let fired = active(&world);
+        assert_eq!(fired.len(), 2);
+
+        world.write_resource::<ProjectilePool>().release(fired[0]);
+        run(&mut world);
+        assert_eq!(active(&world), vec![fired[1]]);
+        assert_eq!(world.read_resource::<ProjectilePool>().free_count(), 1);
+
+        world.write_resource::<ProjectilePool>().request(spawn());
+        run(&mut world);
+        let mut refired = active(&world);
+        refired.sort();
+        let mut expected = fired.clone();
+        expected.sort();
+        assert_eq!(refired, expected);
+        assert_eq!(world.read_resource::<ProjectilePool>().free_count(), 0);
+        assert_eq!(
+            world
+                .read_storage::<Pooled>()
+                .get(fired[0])
+                .unwrap()
+                .generation,
+            1
+        );
+        assert_eq!(
+            world
+                .read_storage::<Pooled>()
+                .get(fired[1])
+                .unwrap()
+                .generation,
+            0
+        );
+    }
+
+    #[test]
+    fn releasing_twice_frees_once() {
+        let mut world = setup();
+        world.write_resource::<ProjectilePool>().request(spawn());
+        run(&mut world);
+        let fired = active(&world)[0];
+
+        {
+            let mut pool = world.write_resource::<ProjectilePool>();
+            pool.release(fired);
+            pool.release(fired);
+        }
+        run(&mut world);
+        assert!(active(&world).is_empty());
+        assert_eq!(world.read_resource::<ProjectilePool>().free_count(), 1);
+    }
+}
@@ -0,0 +1,471 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use crate::ecs::animation::*;
+use crate::ecs::bullet::BulletTypes;
+use crate::ecs::collision::*;
+use crate::ecs::components::*;
+use crate::ecs::enemy::*;
+use crate::ecs::particle::ParticleEmitter;
+use crate::ecs::pickup::*;
+use crate::ecs::player::*;
+use crate::ecs::pool::ProjectileSpawn;
+use crate::ecs::weapon::*;
+use crate::vec2::Vec2;
+
+fn unscaled() -> Vec2 {
+    Vec2 { x: 1.0, y: 1.0 }
+}
+
+fn centered() -> Vec2 {
+    Vec2 { x: 0.5, y: 0.5 }
+}
+
+fn untinted() -> (u8, u8, u8) {
+    (255, 255, 255)
+}
+
+fn opaque() -> u8 {
+    255
+}
+
+/// What happens when an entity spawned from a prefab dies
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum OnDeath {
+    Nothing,
+    Enemy,
+    Boss,
+    Player,
+    Bullet,
+}
+
+impl OnDeath {
+    fn handler(self) -> DeathHandler {
+        match self {
+            OnDeath::Nothing => |_, _, _, _| {},
+            OnDeath::Enemy => on_enemy_ded,
+            OnDeath::Boss => crate::ecs::boss::on_boss_ded,
+            OnDeath::Player => on_player_ded,
+            OnDeath::Bullet => on_bullet_dead,
+        }
+    }
+}
+
+/// Prefabs the game itself spawns, rather than data files
+const SPAWNED_BY_NAME: [&str; 8] = [
+    "player",
+    PLAYER_BULLET_PREFAB,
+    ENEMY_BULLET_PREFAB,
+    "explosion",
+    "big_explosion",
+    "small_explosion",
+    "score_popup",
+    "power_pickup",
+];
+
+/// A component and its starting values, as written in prefab files
+#[derive(Clone, Debug, Deserialize)]
+pub enum ComponentData {
+    Sprite {
+        /// Path of the spritesheet, as listed in `SPRITE_PATHS`
+        sprite: String,
+        #[serde(skip)]
+        spritesheet: usize,
+        /// x, y, width and height of the region drawn from the spritesheet
+        src_rect: (i32, i32, u32, u32),
+        /// Width and height on screen
+        size: (i32, i32),
+        layer: RenderLayer,
+        #[serde(default)]
+        z: f32,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default)]
+        align_to_velocity: bool,
+        #[serde(default)]
+        flip_x: bool,
+        #[serde(default)]
+        flip_y: bool,
+        #[serde(default = "unscaled")]
+        scale: Vec2,
+        #[serde(default = "centered")]
+        pivot: Vec2,
+        /// Red, green and blue multiplied with the sprite's colors
+        #[serde(default = "untinted")]
+        tint: (u8, u8, u8),
+        #[serde(default = "opaque")]
+        alpha: u8,
+        #[serde(default = "crate::assets::blend")]
+        blend: SpriteBlend,
+    },
+    Velocity(Vec2),
+    Collider {
+        radius: f32,
+        layer: Layers,
+        collides_with: Layers,
+    },
+    Health {
+        health: u32,
+        on_death: OnDeath,
+    },
+    HealthBar {
+        width: u32,
+        height: u32,
+        offset: f32,
+    },
+    Damage(u32),
+    Lifetime(f32),
+    Animation {
+        fps: u32,
+        columns: u32,
+        rows: u32,
+    },
+    /// Seconds before the end of its `Lifetime` the sprite starts fading
+    FadeOut(f32),
+    ParticleEmitter(ParticleEmitter),
+    Enemy {
+        speed: f32,
+        #[serde(default)]
+        attack: Option<AttackConfig>,
+        #[serde(default = "crate::assets::one")]
+        cooldown_scale: f32,
+        /// Score for killing it
+        #[serde(default)]
+        points: u32,
+    },
+    Weapon {
+        speed: f32,
+        time_between_shots: f32,
+        damage: u32,
+        bullet_type: String,
+        #[serde(default)]
+        aim: Aim,
+        #[serde(default)]
+        scales_with_rank: bool,
+    },
+    Pickup {
+        kind: PickupKind,
+        radius: f32,
+    },
+    Player,
+    KeyboardControlled,
+    NoCull,
+    YSort,
+}
+
+impl ComponentData {
+    fn insert(&self, entity: Entity, world: &LazyUpdate) {
+        match self {
+            ComponentData::Sprite { .. } => {
+                world.insert(entity, self.sprite().expect("is a sprite"))
+            }
+            ComponentData::Velocity(velocity) => world.insert(
+                entity,
+                Velocity {
+                    velocity: *velocity,
+                },
+            ),
+            ComponentData::Collider { .. } => {
+                world.insert(entity, self.collider().expect("is a collider"))
+            }
+            ComponentData::Health { health, on_death } => {
+                world.insert(entity, Health::new(*health, on_death.handler()))
+            }
+            ComponentData::HealthBar {
+                width,
+                height,
+                offset,
+            } => world.insert(
+                entity,
+                HealthBar {
+                    width: *width,
+                    height: *height,
+                    offset: *offset,
+                },
+            ),
+            ComponentData::Damage(damage) => world.insert(entity, Damage::new(*damage)),
+            ComponentData::Lifetime(time_left) => world.insert(
+                entity,
+                Lifetime {
+                    time_left: *time_left,
+                },
+            ),
+            ComponentData::Animation { fps, columns, rows } => {
+                world.insert(entity, Animation::new(*fps, *columns, *rows))
+            }
+            ComponentData::FadeOut(duration) => world.insert(
+                entity,
+                FadeOut {
+                    duration: *duration,
+                },
+            ),
+            ComponentData::ParticleEmitter(emitter) => world.insert(entity, emitter.clone()),
+            ComponentData::Enemy {
+                speed,
+                attack,
+                cooldown_scale,
+                points,
+            } => world.insert(
+                entity,
+                Enemy::new(*speed, attack.clone())
+                    .with_cooldown_scale(*cooldown_scale)
+                    .with_points(*points),
+            ),
+            ComponentData::Weapon {
+                speed,
+                time_between_shots,
+                damage,
+                bullet_type,
+                aim,
+                scales_with_rank,
+            } => {
+                let mut weapon = Weapon::new(*speed, *time_between_shots, *damage)
+                    .with_bullet_type(bullet_type)
+                    .with_aim(*aim);
+                if *scales_with_rank {
+                    weapon = weapon.with_rank_scaling();
+                }
+                world.insert(entity, weapon);
+            }
+            ComponentData::Pickup { kind, radius } => world.insert(
+                entity,
+                Pickup {
+                    kind: *kind,
+                    radius: *radius,
+                },
+            ),
+            ComponentData::Player => world.insert(entity, Player::new()),
+            ComponentData::KeyboardControlled => world.insert(entity, KeyboardControlled),
+            ComponentData::NoCull => world.insert(entity, NoCull),
+            ComponentData::YSort => world.insert(entity, YSort),
+        }
+    }
+
+    fn sprite(&self) -> Option<Sprite> {
+        match self {
+            ComponentData::Sprite {
+                spritesheet,
+                src_rect: (x, y, width, height),
+                size: (size_x, size_y),
+                layer,
+                z,
+                rotation,
+                align_to_velocity,
+                flip_x,
+                flip_y,
+                scale,
+                pivot,
+                tint: (red, green, blue),
+                alpha,
+                blend,
+                ..
+            } => Some(Sprite {
+                z: *z,
+                rotation: *rotation,
+                align_to_velocity: *align_to_velocity,
+                flip_x: *flip_x,
+                flip_y: *flip_y,
+                scale: *scale,
+                pivot: *pivot,
+                tint: sdl2::pixels::Color::RGB(*red, *green, *blue),
+                alpha: *alpha,
+                blend: *blend,
+                ..Sprite::new(
+                    *spritesheet,
+                    sdl2::rect::Rect::new(*x, *y, *width, *height),
+                    sdl2::rect::Point::new(*size_x, *size_y),
+                    *layer,
+                )
+            }),
+            _ => None,
+        }
+    }
+
+    fn collider(&self) -> Option<CircleCollider> {
+        match self {
+            ComponentData::Collider {
+                radius,
+                layer,
+                collides_with,
+            } => Some(CircleCollider {
+                radius: *radius,
+                layer: LayerMask::from_enum(*layer),
+                collides_with: LayerMask::from_enum(*collides_with),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Named entity templates loaded from a prefab file
+#[derive(Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Vec<ComponentData>>,
+}
+
+impl Prefabs {
+    pub fn load(path: &str) -> Result<Prefabs, String> {
+        let mut prefabs: HashMap<String, Vec<ComponentData>> = crate::assets::load_ron(path)?;
+
+        for (name, components) in prefabs.iter_mut() {
+            for component in components {
+                if let ComponentData::Sprite {
+                    sprite,
+                    spritesheet,
+                    src_rect: (x, y, width, height),
+                    ..
+                } = component
+                {
+                    *spritesheet = crate::assets::sprite_id(sprite)
+                        .ok_or_else(|| format!("{}: unknown sprite {}", path, sprite))?;
+                    let (image_width, image_height) = crate::assets::image_size(sprite)?;
+                    if *x < 0
+                        || *y < 0
+                        || (*x as u32).saturating_add(*width) > image_width
+                        || (*y as u32).saturating_add(*height) > image_height
+                    {
+                        return Err(format!(
+                            "{}: prefab {} has a src_rect outside of {}",
+                            path, name, sprite
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(Prefabs { prefabs })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    /// Checks that the prefabs the game spawns by name exist, that projectiles
+    /// have what they are fired with, and that weapons name known bullet types
+    pub fn validate(&self, bullet_types: &BulletTypes) -> Result<(), String> {
+        for name in SPAWNED_BY_NAME.iter() {
+            if !self.contains(name) {
+                return Err(format!("missing prefab {}", name));
+            }
+        }
+
+        for name in [PLAYER_BULLET_PREFAB, ENEMY_BULLET_PREFAB].iter() {
+            let components = self.get(name);
+            let has = |is: fn(&ComponentData) -> bool| components.iter().any(is);
+            if !has(|c| matches!(c, ComponentData::Sprite { .. }))
+                || !has(|c| matches!(c, ComponentData::Velocity(_)))
+                || !has(|c| matches!(c, ComponentData::Collider { .. }))
+                || !has(|c| matches!(c, ComponentData::Health { .. }))
+                || !has(|c| matches!(c, ComponentData::Damage(_)))
+            {
+                return Err(format!(
+                    "prefab {}: projectiles need a sprite, velocity, collider, health and damage",
+                    name
+                ));
+            }
+        }
+
+        for (name, components) in self.prefabs.iter() {
+            for component in components {
+                if let ComponentData::Weapon { bullet_type, .. } = component {
+                    if !bullet_types.contains(bullet_type) {
+                        return Err(format!(
+                            "prefab {}: unknown bullet type {}",
+                            name, bullet_type
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Names are checked by `validate` and `Stage::validate` at startup
+    fn get(&self, name: &str) -> &[ComponentData] {
+        &self.prefabs[name]
+    }
+
+    /// Creates an entity at `position` with the components of the prefab
+    pub fn spawn(
+        &self,
+        name: &str,
+        position: Vec2,
+        entities: &Entities,
+        world: &LazyUpdate,
+    ) -> Entity {
+        let entity = entities.create();
+        world.insert(entity, Position { position });
+        for component in self.get(name) {
+            component.insert(entity, world);
+        }
+        entity
+    }
+
+    /// Like `spawn`, but passes every component through `overrides` first so
+    /// the caller can change its values
+    pub fn spawn_with<F>(
+        &self,
+        name: &str,
+        position: Vec2,
+        entities: &Entities,
+        world: &LazyUpdate,
+        mut overrides: F,
+    ) -> Entity
+    where
+        F: FnMut(&mut ComponentData),
+    {
+        let entity = entities.create();
+        world.insert(entity, Position { position });
+        for component in self.get(name) {
+            let mut component = component.clone();
+            overrides(&mut component);
+            component.insert(entity, world);
+        }
+        entity
+    }
+
+    /// Builds the components of a pooled projectile from the prefab `name`,
+    /// passing them through `overrides` like `spawn_with`. The prefab needs a
+    /// sprite, a velocity, a collider, health and damage.
+    pub fn projectile_with<F>(
+        &self,
+        name: &str,
+        position: Vec2,
+        mut overrides: F,
+    ) -> ProjectileSpawn
+    where
+        F: FnMut(&mut ComponentData),
+    {
+        let (mut sprite, mut velocity, mut collider, mut health, mut damage) =
+            (None, None, None, None, None);
+        for component in self.get(name) {
+            let mut component = component.clone();
+            overrides(&mut component);
+            match component {
+                ComponentData::Sprite { .. } => sprite = component.sprite(),
+                ComponentData::Velocity(velocity_data) => {
+                    velocity = Some(Velocity {
+                        velocity: velocity_data,
+                    })
+                }
+                ComponentData::Collider { .. } => collider = component.collider(),
+                ComponentData::Health {
+                    health: amount,
+                    on_death,
+                } => health = Some(Health::new(amount, on_death.handler())),
+                ComponentData::Damage(amount) => damage = Some(Damage::new(amount)),
+                _ => {}
+            }
+        }
+        let checked = "projectile prefabs are checked at startup";
+        ProjectileSpawn {
+            position: Position { position },
+            sprite: sprite.expect(checked),
+            velocity: velocity.expect(checked),
+            collider: collider.expect(checked),
+            health: health.expect(checked),
+            damage: damage.expect(checked),
+        }
+    }
+}
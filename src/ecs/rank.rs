@@ -0,0 +1,94 @@
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+
+use crate::ecs::resources::*;
+use crate::ecs::weapon::*;
+
+const RANK_PER_KILL: f32 = 0.004;
+/// Rank gained per second alive, doubled after a minute without dying
+const RANK_PER_SECOND: f32 = 0.002;
+/// Rank gained per second at full power
+const RANK_PER_SECOND_AT_MAX_POWER: f32 = 0.002;
+const RANK_LOST_ON_DEATH: f32 = 0.15;
+const RANK_PER_STAGE_LOOP: f32 = 0.1;
+
+/// How hard the game currently is, between 0 and 1. Rises as the player does
+/// well and falls when they die.
+#[derive(Default)]
+pub struct Rank {
+    pub value: f32,
+}
+
+impl Rank {
+    fn add(&mut self, amount: f32) {
+        self.value = (self.value + amount).clamp(0.0, 1.0);
+    }
+
+    /// Multiplier on the speed of enemy bullets
+    pub fn bullet_speed(&self) -> f32 {
+        1.0 + 0.5 * self.value
+    }
+
+    /// Multiplier on how often enemies shoot, cooldowns are divided by this
+    pub fn fire_rate(&self) -> f32 {
+        1.0 + 0.75 * self.value
+    }
+
+    /// Extra bullets added to enemy spread patterns
+    pub fn extra_bullets(&self) -> u32 {
+        (self.value * 4.0) as u32
+    }
+
+    /// Multiplier on the health enemies spawn with
+    pub fn enemy_health(&self) -> f32 {
+        1.0 + self.value
+    }
+}
+
+/// Raises and lowers the rank based on how the player is doing
+#[derive(Default)]
+pub struct RankSystem {
+    reader: Option<ReaderId<GameEvent>>,
+    time_since_death: f32,
+}
+
+impl<'a> System<'a> for RankSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        Read<'a, EventChannel<GameEvent>>,
+        Read<'a, PlayerStats>,
+        Write<'a, Rank>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (delta_time, events, stats, mut rank) = data;
+        let delta_time = delta_time.0;
+
+        let reader = self.reader.as_mut().expect("RankSystem was not set up");
+        for event in events.read(reader) {
+            match event {
+                GameEvent::EnemyKilled(..) => rank.add(RANK_PER_KILL),
+                GameEvent::PlayerDied(_) => {
+                    rank.add(-RANK_LOST_ON_DEATH);
+                    self.time_since_death = 0.0;
+                }
+                GameEvent::StageLooped(_) => rank.add(RANK_PER_STAGE_LOOP),
+                GameEvent::Grazed(_) | GameEvent::Bonus(..) => {}
+            }
+        }
+
+        self.time_since_death += delta_time;
+        let streak = 1.0 + (self.time_since_death / 60.0).min(1.0);
+        let power = stats.power as f32 / MAX_POWER as f32;
+        rank.add((RANK_PER_SECOND * streak + RANK_PER_SECOND_AT_MAX_POWER * power) * delta_time);
+    }
+}
@@ -1,29 +1,584 @@
 use sdl2::pixels::Color;
-use sdl2::render::{Texture, WindowCanvas};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, WindowCanvas};
 use specs::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 
+use crate::atlas::{Atlas, TextureStyle};
+use crate::ecs::background::*;
+use crate::ecs::boss::*;
+use crate::ecs::camera::Camera;
+use crate::ecs::collision::*;
 use crate::ecs::components::*;
+use crate::ecs::debug::*;
+use crate::ecs::font::BitmapFont;
+use crate::ecs::hud::*;
+use crate::ecs::particle::Particles;
+use crate::ecs::score::*;
+use crate::vec2::Vec2;
 
-pub type SystemData<'a> = (ReadStorage<'a, Position>, ReadStorage<'a, Sprite>);
+pub type SystemData<'a> = (
+    ReadStorage<'a, Position>,
+    ReadStorage<'a, Sprite>,
+    ReadStorage<'a, YSort>,
+    ReadStorage<'a, Velocity>,
+    ReadStorage<'a, Boss>,
+    ReadStorage<'a, Health>,
+    ReadStorage<'a, ScorePopup>,
+    ReadExpect<'a, Camera>,
+    Read<'a, Background>,
+    ReadExpect<'a, BitmapFont>,
+    Read<'a, Hud>,
+    ReadStorage<'a, HealthBar>,
+    (
+        Entities<'a>,
+        ReadStorage<'a, CircleCollider>,
+        ReadStorage<'a, Projectile>,
+        Read<'a, DebugOverlay>,
+    ),
+    Read<'a, Particles>,
+);
+
+/// Particles drawn with a single call because they share a layer, blend mode
+/// and color
+type ParticleBatch = ((RenderLayer, SpriteBlend, Color), Vec<Rect>);
+
+const BOSS_BAR_MARGIN: i32 = 40;
+const BOSS_BAR_HEIGHT: u32 = 12;
+
+/// Screen pixels per font pixel of score popups
+const POPUP_TEXT_SCALE: u32 = 2;
+
+/// Line segments the debug overlay approximates a collider's circle with
+const DEBUG_CIRCLE_SEGMENTS: usize = 16;
 
 pub fn render(
     canvas: &mut WindowCanvas,
     background: Color,
-    textures: &[Texture],
+    atlas: &mut Atlas,
     data: SystemData,
 ) -> Result<(), String> {
     canvas.set_draw_color(background);
     canvas.clear();
-    for (pos, sprite) in (&data.0, &data.1).join() {
-        let draw_x = pos.x() as i32 - (sprite.size.x / 2);
-        let draw_y = pos.y() as i32 - (sprite.size.y / 2);
 
-        let destination =
-            sdl2::rect::Rect::new(draw_x, draw_y, sprite.size.x as u32, sprite.size.y as u32);
-        canvas.copy(&textures[sprite.spritesheet], sprite.src_rect, destination)?;
+    let camera = &*data.7;
+    draw_background(canvas, atlas, &data.8, camera)?;
+
+    // Join order is by entity id, the stable sort keeps ties in that order so
+    // they do not flicker between frames. Sprites that would be drawn at the
+    // same depth are grouped by atlas page and blend mode, so SDL can draw
+    // runs of them in one batch.
+    let mut sprites: Vec<_> = (&data.0, &data.1, data.2.maybe(), data.3.maybe())
+        .join()
+        .collect();
+    sprites.sort_by(|(a_pos, a, a_y_sort, _), (b_pos, b, b_y_sort, _)| {
+        let a_y = a_y_sort.map_or(0.0, |_| a_pos.y());
+        let b_y = b_y_sort.map_or(0.0, |_| b_pos.y());
+        a.layer
+            .cmp(&b.layer)
+            .then(a.z.partial_cmp(&b.z).unwrap_or(Ordering::Equal))
+            .then(a_y.partial_cmp(&b_y).unwrap_or(Ordering::Equal))
+            .then(
+                atlas
+                    .region(a.spritesheet)
+                    .page
+                    .cmp(&atlas.region(b.spritesheet).page),
+            )
+            .then(a.blend.cmp(&b.blend))
+    });
+
+    let batches = batch_particles(camera, &data.13);
+    let mut batches = batches.iter().peekable();
+    for (pos, sprite, _, velocity) in sprites {
+        while let Some(batch) = batches.next_if(|((layer, _, _), _)| *layer < sprite.layer) {
+            draw_particle_batch(canvas, batch)?;
+        }
+        // The HUD stays put while the camera moves and shakes
+        let camera = Some(camera).filter(|_| sprite.layer != RenderLayer::Hud);
+        draw_sprite(canvas, atlas, camera, pos, sprite, velocity)?;
+    }
+    for batch in batches {
+        draw_particle_batch(canvas, batch)?;
+    }
+
+    for (pos, health, bar) in (&data.0, &data.5, &data.11).join() {
+        if health.health < health.max_health {
+            draw_health_bar(canvas, camera, pos, health, bar)?;
+        }
+    }
+
+    for (boss, health) in (&data.4, &data.5).join() {
+        draw_boss_bar(canvas, boss, health)?;
+    }
+
+    let font = &*data.9;
+    for (pos, popup) in (&data.0, &data.6).join() {
+        let screen = camera.world_to_screen(pos.position);
+        let text = popup.points.to_string();
+        let (width, height) = font.measure(&text);
+        draw_text(
+            canvas,
+            atlas,
+            font,
+            &text,
+            screen.x as i32 - (width * POPUP_TEXT_SCALE) as i32 / 2,
+            screen.y as i32 - (height * POPUP_TEXT_SCALE) as i32 / 2,
+            POPUP_TEXT_SCALE,
+            Color::WHITE,
+        )?;
+    }
+
+    let (_, _, _, debug_overlay) = &data.12;
+    if debug_overlay.enabled {
+        draw_debug_overlay(canvas, atlas, font, camera, &data)?;
+    }
+
+    let (screen_width, screen_height) = canvas.output_size()?;
+    for element in data.10.elements.iter() {
+        let (width, height) = font.measure(&element.text);
+        let (width, height) = (width * element.scale, height * element.scale);
+        let x = match element.anchor {
+            Anchor::TopLeft | Anchor::BottomLeft => element.offset.x as i32,
+            Anchor::TopRight | Anchor::BottomRight => {
+                (screen_width - width) as i32 - element.offset.x as i32
+            }
+        };
+        let y = match element.anchor {
+            Anchor::TopLeft | Anchor::TopRight => element.offset.y as i32,
+            Anchor::BottomLeft | Anchor::BottomRight => {
+                (screen_height - height) as i32 - element.offset.y as i32
+            }
+        };
+        let (red, green, blue) = element.color;
+        draw_text(
+            canvas,
+            atlas,
+            font,
+            &element.text,
+            x,
+            y,
+            element.scale,
+            Color::RGB(red, green, blue),
+        )?;
     }
 
     canvas.present();
 
     Ok(())
 }
+
+/// Tiles every background layer across the screen, back to front
+fn draw_background(
+    canvas: &mut WindowCanvas,
+    atlas: &mut Atlas,
+    background: &Background,
+    camera: &Camera,
+) -> Result<(), String> {
+    let (screen_width, screen_height) = canvas.output_size()?;
+
+    for layer in background.layers.iter() {
+        let region = atlas.region(layer.spritesheet);
+        let texture = atlas.texture(region.page, TextureStyle::default());
+        let (width, height) = layer.size;
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        // Start one tile up and left of the screen so scrolling never shows
+        // a gap along the edges
+        let shift = layer.offset + camera.parallax_offset(layer.parallax);
+        let start_x = shift.x.rem_euclid(width as f32) as i32 - width as i32;
+        let start_y = shift.y.rem_euclid(height as f32) as i32 - height as i32;
+
+        let mut y = start_y;
+        while y < screen_height as i32 {
+            let mut x = start_x;
+            while x < screen_width as i32 {
+                canvas.copy(texture, region.rect, Rect::new(x, y, width, height))?;
+                x += width as i32;
+            }
+            y += height as i32;
+        }
+    }
+
+    Ok(())
+}
+
+fn blend_mode(blend: SpriteBlend) -> BlendMode {
+    match blend {
+        SpriteBlend::Blend => BlendMode::Blend,
+        SpriteBlend::Additive => BlendMode::Add,
+        SpriteBlend::Modulate => BlendMode::Mod,
+    }
+}
+
+/// Groups particles that look the same, ordered by layer
+fn batch_particles(camera: &Camera, particles: &Particles) -> Vec<ParticleBatch> {
+    let mut batches: HashMap<_, Vec<Rect>> = HashMap::new();
+    for particle in particles.iter() {
+        let screen = camera.world_to_screen(particle.position);
+        let size = ((particle.size() * camera.zoom) as u32).max(1);
+        let style = &particle.style;
+        batches
+            .entry((style.layer, style.blend, particle.color()))
+            .or_default()
+            .push(Rect::new(
+                screen.x as i32 - size as i32 / 2,
+                screen.y as i32 - size as i32 / 2,
+                size,
+                size,
+            ));
+    }
+
+    let mut batches: Vec<_> = batches.into_iter().collect();
+    batches.sort_by_key(|((layer, _, _), _)| *layer);
+    batches
+}
+
+fn draw_particle_batch(canvas: &mut WindowCanvas, batch: &ParticleBatch) -> Result<(), String> {
+    let ((_, blend, color), rects) = batch;
+    canvas.set_blend_mode(blend_mode(*blend));
+    canvas.set_draw_color(*color);
+    let result = canvas.fill_rects(rects);
+    canvas.set_blend_mode(BlendMode::None);
+    result
+}
+
+/// Draws `sprite` through `camera`, or straight to the screen without one
+fn draw_sprite(
+    canvas: &mut WindowCanvas,
+    atlas: &mut Atlas,
+    camera: Option<&Camera>,
+    pos: &Position,
+    sprite: &Sprite,
+    velocity: Option<&Velocity>,
+) -> Result<(), String> {
+    let (screen, zoom) = match camera {
+        Some(camera) => (camera.world_to_screen(pos.position), camera.zoom),
+        None => (pos.position, 1.0),
+    };
+    let width = (sprite.size.x as f32 * sprite.scale.x.abs() * zoom) as u32;
+    let height = (sprite.size.y as f32 * sprite.scale.y.abs() * zoom) as u32;
+    let pivot = Point::new(
+        (width as f32 * sprite.pivot.x) as i32,
+        (height as f32 * sprite.pivot.y) as i32,
+    );
+    let destination = Rect::new(
+        screen.x as i32 - pivot.x(),
+        screen.y as i32 - pivot.y(),
+        width,
+        height,
+    );
+
+    let mut rotation = sprite.rotation;
+    if let Some(velocity) = velocity.filter(|_| sprite.align_to_velocity) {
+        let direction = velocity.velocity;
+        if direction.squared_length() > 0.0 {
+            // Sprites face up, which is negative y on screen
+            rotation += direction.x.atan2(-direction.y).to_degrees();
+        }
+    }
+
+    // Negative scale mirrors the sprite, same as flipping it
+    let flip_x = sprite.flip_x != (sprite.scale.x < 0.0);
+    let flip_y = sprite.flip_y != (sprite.scale.y < 0.0);
+
+    let (page, src_rect, visible) = match atlas.locate(sprite.spritesheet, sprite.src_rect) {
+        Some(located) => located,
+        None => return Ok(()),
+    };
+    // Only part of the sprite is on its spritesheet, that part is drawn where
+    // it would be and as big as it would be if the whole sprite was
+    let (destination, pivot) = if visible.size() == sprite.src_rect.size() {
+        (destination, pivot)
+    } else {
+        let scale_x = width as f32 / sprite.src_rect.width() as f32;
+        let scale_y = height as f32 / sprite.src_rect.height() as f32;
+        let visible_width = visible.width() as f32 * scale_x;
+        let visible_height = visible.height() as f32 * scale_y;
+        let mut left = visible.x() as f32 * scale_x;
+        let mut top = visible.y() as f32 * scale_y;
+        if flip_x {
+            left = width as f32 - left - visible_width;
+        }
+        if flip_y {
+            top = height as f32 - top - visible_height;
+        }
+        (
+            Rect::new(
+                destination.x() + left as i32,
+                destination.y() + top as i32,
+                visible_width as u32,
+                visible_height as u32,
+            ),
+            Point::new(pivot.x() - left as i32, pivot.y() - top as i32),
+        )
+    };
+    let texture = atlas.texture(
+        page,
+        TextureStyle {
+            tint: sprite.tint,
+            alpha: sprite.alpha,
+            blend: blend_mode(sprite.blend),
+        },
+    );
+
+    if rotation == 0.0 && !flip_x && !flip_y {
+        canvas.copy(texture, src_rect, destination)
+    } else {
+        canvas.copy_ex(
+            texture,
+            src_rect,
+            destination,
+            rotation as f64,
+            pivot,
+            flip_x,
+            flip_y,
+        )
+    }
+}
+
+/// Small bar above an entity, going from green to red as it loses health
+fn draw_health_bar(
+    canvas: &mut WindowCanvas,
+    camera: &Camera,
+    pos: &Position,
+    health: &Health,
+    bar: &HealthBar,
+) -> Result<(), String> {
+    let above = Vec2 {
+        x: pos.x(),
+        y: pos.y() - bar.offset,
+    };
+    let screen = camera.world_to_screen(above);
+    let width = (bar.width as f32 * camera.zoom) as u32;
+    let height = ((bar.height as f32 * camera.zoom) as u32).max(1);
+    let left = screen.x as i32 - width as i32 / 2;
+    let top = screen.y as i32 - height as i32 / 2;
+    let fraction = health.fraction();
+
+    canvas.set_draw_color(Color::RGB(40, 40, 40));
+    canvas.fill_rect(Rect::new(left, top, width, height))?;
+    canvas.set_draw_color(Color::RGB(
+        (255.0 * (1.0 - fraction)) as u8,
+        (255.0 * fraction) as u8,
+        0,
+    ));
+    canvas.fill_rect(Rect::new(
+        left,
+        top,
+        (width as f32 * fraction) as u32,
+        height,
+    ))?;
+
+    Ok(())
+}
+
+/// Health bar across the top of the screen with a tick for every phase and
+/// the time left in the current phase underneath
+fn draw_boss_bar(canvas: &mut WindowCanvas, boss: &Boss, health: &Health) -> Result<(), String> {
+    let (width, _) = canvas.output_size()?;
+    let bar_width = width.saturating_sub(BOSS_BAR_MARGIN as u32 * 2);
+    let fraction = health.fraction();
+
+    canvas.set_draw_color(Color::RGB(60, 0, 0));
+    canvas.fill_rect(Rect::new(
+        BOSS_BAR_MARGIN,
+        BOSS_BAR_MARGIN / 2,
+        bar_width,
+        BOSS_BAR_HEIGHT,
+    ))?;
+    canvas.set_draw_color(Color::RED);
+    canvas.fill_rect(Rect::new(
+        BOSS_BAR_MARGIN,
+        BOSS_BAR_MARGIN / 2,
+        (bar_width as f32 * fraction) as u32,
+        BOSS_BAR_HEIGHT,
+    ))?;
+
+    canvas.set_draw_color(Color::WHITE);
+    for threshold in boss.thresholds().filter(|threshold| *threshold > 0.0) {
+        let x = BOSS_BAR_MARGIN + (bar_width as f32 * threshold) as i32;
+        canvas.fill_rect(Rect::new(x, BOSS_BAR_MARGIN / 2, 2, BOSS_BAR_HEIGHT))?;
+    }
+
+    canvas.set_draw_color(Color::YELLOW);
+    canvas.fill_rect(Rect::new(
+        BOSS_BAR_MARGIN,
+        BOSS_BAR_MARGIN / 2 + BOSS_BAR_HEIGHT as i32 + 4,
+        (bar_width as f32 * boss.time_left()) as u32,
+        4,
+    ))?;
+
+    Ok(())
+}
+
+/// Draws the spatial grid shaded by how many colliders are in each cell, every
+/// collider colored by its layer, velocities and the ids of everything that is
+/// not a projectile
+fn draw_debug_overlay(
+    canvas: &mut WindowCanvas,
+    atlas: &mut Atlas,
+    font: &BitmapFont,
+    camera: &Camera,
+    data: &SystemData,
+) -> Result<(), String> {
+    let (entities, colliders, projectiles, _) = &data.12;
+    let player_layer = LayerMask::from_enum(Layers::Player);
+    let enemy_layer = LayerMask::from_enum(Layers::Enemy);
+
+    let cell_of = |position: Vec2| {
+        (
+            (position.x / DEBUG_GRID_CELL).floor() as i32,
+            (position.y / DEBUG_GRID_CELL).floor() as i32,
+        )
+    };
+    let mut occupancy: HashMap<(i32, i32), u32> = HashMap::new();
+    for (pos, _) in (&data.0, colliders).join() {
+        *occupancy.entry(cell_of(pos.position)).or_insert(0) += 1;
+    }
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    let cell_size = (DEBUG_GRID_CELL * camera.zoom).ceil() as u32;
+    for (&(x, y), &count) in occupancy.iter() {
+        let corner = camera.world_to_screen(Vec2 {
+            x: x as f32 * DEBUG_GRID_CELL,
+            y: y as f32 * DEBUG_GRID_CELL,
+        });
+        canvas.set_draw_color(Color::RGBA(255, 128, 0, (count * 16).min(128) as u8));
+        canvas.fill_rect(Rect::new(
+            corner.x as i32,
+            corner.y as i32,
+            cell_size,
+            cell_size,
+        ))?;
+    }
+
+    let (screen_width, screen_height) = canvas.output_size()?;
+    let top_left = cell_of(camera.screen_to_world(Vec2::default()));
+    let bottom_right = cell_of(camera.screen_to_world(Vec2 {
+        x: screen_width as f32,
+        y: screen_height as f32,
+    }));
+    canvas.set_draw_color(Color::RGBA(255, 255, 255, 48));
+    for x in top_left.0..=bottom_right.0 + 1 {
+        let screen = camera.world_to_screen(Vec2 {
+            x: x as f32 * DEBUG_GRID_CELL,
+            y: 0.0,
+        });
+        canvas.draw_line(
+            Point::new(screen.x as i32, 0),
+            Point::new(screen.x as i32, screen_height as i32),
+        )?;
+    }
+    for y in top_left.1..=bottom_right.1 + 1 {
+        let screen = camera.world_to_screen(Vec2 {
+            x: 0.0,
+            y: y as f32 * DEBUG_GRID_CELL,
+        });
+        canvas.draw_line(
+            Point::new(0, screen.y as i32),
+            Point::new(screen_width as i32, screen.y as i32),
+        )?;
+    }
+    canvas.set_blend_mode(BlendMode::None);
+
+    for (pos, collider) in (&data.0, colliders).join() {
+        let center = camera.world_to_screen(pos.position);
+        let radius = collider.radius * camera.zoom;
+        let points: Vec<Point> = (0..=DEBUG_CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 / DEBUG_CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+                Point::new(
+                    (center.x + angle.cos() * radius) as i32,
+                    (center.y + angle.sin() * radius) as i32,
+                )
+            })
+            .collect();
+        canvas.set_draw_color(if collider.layer.any(&player_layer) {
+            Color::GREEN
+        } else if collider.layer.any(&enemy_layer) {
+            Color::RED
+        } else {
+            Color::WHITE
+        });
+        canvas.draw_lines(points.as_slice())?;
+    }
+
+    canvas.set_draw_color(Color::YELLOW);
+    for (pos, velocity) in (&data.0, &data.3).join() {
+        let from = camera.world_to_screen(pos.position);
+        let to = camera.world_to_screen(pos.position + velocity.velocity * DEBUG_VELOCITY_SCALE);
+        canvas.draw_line(
+            Point::new(from.x as i32, from.y as i32),
+            Point::new(to.x as i32, to.y as i32),
+        )?;
+    }
+
+    // Labelling every bullet would bury the rest under text
+    for (entity, pos, _) in (entities, &data.0, !projectiles).join() {
+        let screen = camera.world_to_screen(pos.position);
+        draw_text(
+            canvas,
+            atlas,
+            font,
+            &entity.id().to_string(),
+            screen.x as i32,
+            screen.y as i32,
+            1,
+            Color::CYAN,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Draws `text` with its top left at `x`, `y`, `scale` screen pixels per
+/// font pixel
+#[allow(clippy::too_many_arguments)]
+fn draw_text(
+    canvas: &mut WindowCanvas,
+    atlas: &mut Atlas,
+    font: &BitmapFont,
+    text: &str,
+    x: i32,
+    y: i32,
+    scale: u32,
+    color: Color,
+) -> Result<(), String> {
+    let region = atlas.region(font.spritesheet);
+    let texture = atlas.texture(
+        region.page,
+        TextureStyle {
+            tint: color,
+            ..TextureStyle::default()
+        },
+    );
+
+    let (mut pen_x, mut pen_y) = (x, y);
+    for character in text.chars() {
+        if character == '\n' {
+            pen_x = x;
+            pen_y += (font.line_height * scale) as i32;
+            continue;
+        }
+        let glyph = match font.glyph(character) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        canvas.copy(
+            texture,
+            Rect::new(
+                region.rect.x() + glyph.x,
+                region.rect.y() + glyph.y,
+                glyph.width,
+                glyph.height,
+            ),
+            Rect::new(pen_x, pen_y, glyph.width * scale, glyph.height * scale),
+        )?;
+        pen_x += (glyph.advance * scale) as i32;
+    }
+
+    Ok(())
+}
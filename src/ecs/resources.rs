@@ -1,7 +1,60 @@
 use crate::input;
+use crate::vec2::Vec2;
 
 #[derive(Default)]
 pub struct DeltaTime(pub f32);
 
 #[derive(Default)]
 pub struct InputResource(pub input::Input);
+
+/// Things that happened during a frame that several systems react to, sent
+/// through an `EventChannel`
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub enum GameEvent {
+    /// Where the enemy died and the points it was worth
+    EnemyKilled(Vec2, u32),
+    PlayerDied(Vec2),
+    /// An enemy bullet passed close to the player without hitting
+    Grazed(Vec2),
+    /// Flat points scored at a position, not affected by the chain
+    Bonus(Vec2, u32),
+    /// The stage was cleared and starts over, with the new loop count
+    StageLooped(u32),
+}
+
+/// Player progress that outlives a single player entity
+#[derive(Default)]
+pub struct PlayerStats {
+    pub power: u32,
+    /// Lives left, including the current one
+    pub lives: u32,
+    pub bombs: u32,
+}
+
+/// The visible play area, entities further than `margin` outside of it are despawned
+pub struct PlayfieldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub margin: f32,
+}
+
+impl PlayfieldBounds {
+    pub fn new(width: f32, height: f32, margin: f32) -> Self {
+        PlayfieldBounds {
+            min: Vec2::default(),
+            max: Vec2 {
+                x: width,
+                y: height,
+            },
+            margin,
+        }
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        position.x >= self.min.x - self.margin
+            && position.x <= self.max.x + self.margin
+            && position.y >= self.min.y - self.margin
+            && position.y <= self.max.y + self.margin
+    }
+}
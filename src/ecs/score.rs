@@ -0,0 +1,261 @@
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+use std::collections::HashSet;
+
+use crate::ecs::collision::*;
+use crate::ecs::components::*;
+use crate::ecs::player::*;
+use crate::ecs::pool::Pooled;
+use crate::ecs::prefab::Prefabs;
+use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+
+/// Seconds after a kill in which the next one keeps the chain going
+const CHAIN_WINDOW: f32 = 1.5;
+/// Once the window closes the chain loses a kill every this many seconds
+const CHAIN_DECAY_INTERVAL: f32 = 0.1;
+/// Multiplier gained per kill in the chain
+const CHAIN_MULTIPLIER_STEP: f32 = 0.1;
+const MAX_MULTIPLIER: f32 = 8.0;
+/// How close a bullet has to pass the edge of the player's collider to count
+/// as a graze
+const GRAZE_DISTANCE: f32 = 24.0;
+const GRAZE_POINTS: u32 = 10;
+
+/// The player's score and kill chain
+#[derive(Default)]
+pub struct Score {
+    pub score: u64,
+    /// Kills in a row, each within `CHAIN_WINDOW` of the last
+    pub chain: u32,
+    chain_timer: f32,
+}
+
+impl Score {
+    /// Multiplier on points for kills and grazes, grows with the chain
+    pub fn multiplier(&self) -> f32 {
+        (1.0 + self.chain as f32 * CHAIN_MULTIPLIER_STEP).min(MAX_MULTIPLIER)
+    }
+
+    fn add(&mut self, points: u32) {
+        self.score += points as u64;
+    }
+
+    fn decay(&mut self, delta_time: f32) {
+        self.chain_timer -= delta_time;
+        while self.chain_timer <= 0.0 && self.chain > 0 {
+            self.chain -= 1;
+            self.chain_timer += CHAIN_DECAY_INTERVAL;
+        }
+        if self.chain == 0 {
+            self.chain_timer = 0.0;
+        }
+    }
+}
+
+/// Points floating up from where they were scored
+#[derive(Debug)]
+pub struct ScorePopup {
+    pub points: u32,
+}
+
+impl Component for ScorePopup {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Sends a `GameEvent::Grazed` for every enemy bullet that comes close to the
+/// player, once per bullet
+#[derive(Default)]
+pub struct GrazeSystem {
+    /// Bullets close to the player, with their pool generation
+    grazing: HashSet<(Entity, u32)>,
+}
+
+impl<'a> System<'a> for GrazeSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, CircleCollider>,
+        ReadStorage<'a, Pooled>,
+        Write<'a, EventChannel<GameEvent>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            position_storage,
+            player_storage,
+            projectile_storage,
+            collider_storage,
+            pooled_storage,
+            mut events,
+        ) = data;
+
+        let player = (&position_storage, &player_storage, &collider_storage)
+            .join()
+            .next()
+            .map(|(position, _, collider)| (position.position, collider.radius));
+        let (player_position, player_radius) = match player {
+            Some(player) => player,
+            None => {
+                self.grazing.clear();
+                return;
+            }
+        };
+
+        // Bullets stay in the set while they are close, so passing by only
+        // counts once. A pooled entity can be released and fired again before
+        // it leaves the set, so bullets are told apart by pool generation too.
+        let enemy_layer = LayerMask::from_enum(Layers::Enemy);
+        let mut grazing = HashSet::new();
+        for (entity, position, _, collider) in (
+            &entities,
+            &position_storage,
+            &projectile_storage,
+            &collider_storage,
+        )
+            .join()
+        {
+            if !collider.layer.any(&enemy_layer) {
+                continue;
+            }
+            let bullet = (
+                entity,
+                pooled_storage
+                    .get(entity)
+                    .map_or(0, |pooled| pooled.generation),
+            );
+            let distance = Vec2::distance(player_position, position.position);
+            let touching = player_radius + collider.radius;
+            if distance > touching + GRAZE_DISTANCE {
+                continue;
+            }
+            // A bullet that hits the player this frame is no graze, it stays in
+            // the set so it cannot count as one later either
+            if distance >= touching && !self.grazing.contains(&bullet) {
+                events.single_write(GameEvent::Grazed(position.position));
+            }
+            grazing.insert(bullet);
+        }
+        self.grazing = grazing;
+    }
+}
+
+/// Adds up points from kills, grazes and bonuses and keeps the chain going
+#[derive(Default)]
+pub struct ScoreSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'a> System<'a> for ScoreSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, EventChannel<GameEvent>>,
+        Read<'a, Prefabs>,
+        Write<'a, Score>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, delta_time, events, prefabs, mut score, world) = data;
+
+        score.decay(delta_time.0);
+
+        let reader = self.reader.as_mut().expect("ScoreSystem was not set up");
+        for event in events.read(reader) {
+            match *event {
+                GameEvent::EnemyKilled(position, points) => {
+                    score.chain += 1;
+                    score.chain_timer = CHAIN_WINDOW;
+                    let points = (points as f32 * score.multiplier()) as u32;
+                    score.add(points);
+                    spawn_popup(points, position, &prefabs, &entities, &world);
+                }
+                GameEvent::Grazed(_) => {
+                    let points = (GRAZE_POINTS as f32 * score.multiplier()) as u32;
+                    score.add(points);
+                }
+                GameEvent::Bonus(position, points) => {
+                    score.add(points);
+                    spawn_popup(points, position, &prefabs, &entities, &world);
+                }
+                GameEvent::PlayerDied(_) => {
+                    score.chain = 0;
+                    score.chain_timer = 0.0;
+                }
+                GameEvent::StageLooped(_) => {}
+            }
+        }
+    }
+}
+
+fn spawn_popup(
+    points: u32,
+    position: Vec2,
+    prefabs: &Prefabs,
+    entities: &specs::Entities,
+    world: &specs::LazyUpdate,
+) {
+    if points == 0 {
+        return;
+    }
+    let popup = prefabs.spawn("score_popup", position, entities, world);
+    world.insert(popup, ScorePopup { points });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain_of(chain: u32) -> Score {
+        Score {
+            score: 0,
+            chain,
+            chain_timer: CHAIN_WINDOW,
+        }
+    }
+
+    #[test]
+    fn chain_holds_while_the_window_is_open() {
+        let mut score = chain_of(5);
+        score.decay(CHAIN_WINDOW - 0.05);
+        assert_eq!(score.chain, 5);
+    }
+
+    #[test]
+    fn chain_loses_a_kill_every_interval_after_the_window() {
+        let mut score = chain_of(5);
+        score.decay(CHAIN_WINDOW + CHAIN_DECAY_INTERVAL * 2.5);
+        assert_eq!(score.chain, 2);
+    }
+
+    #[test]
+    fn chain_bottoms_out_at_zero() {
+        let mut score = chain_of(3);
+        score.decay(100.0);
+        assert_eq!(score.chain, 0);
+        assert_eq!(score.chain_timer, 0.0);
+        score.decay(1.0);
+        assert_eq!(score.chain, 0);
+    }
+
+    #[test]
+    fn multiplier_grows_with_the_chain_up_to_the_cap() {
+        assert_eq!(chain_of(0).multiplier(), 1.0);
+        assert!((chain_of(10).multiplier() - 2.0).abs() < 1e-6);
+        assert_eq!(chain_of(1000).multiplier(), MAX_MULTIPLIER);
+    }
+}
@@ -0,0 +1,136 @@
+use serde::Deserialize;
+
+use crate::ecs::behavior::Behaviors;
+use crate::ecs::boss::Bosses;
+use crate::ecs::enemy::AttackConfig;
+use crate::ecs::formation::FormationConfig;
+use crate::ecs::path::PathConfig;
+use crate::ecs::prefab::Prefabs;
+use crate::ecs::weapon::Aim;
+use crate::vec2::Vec2;
+
+/// A group of enemies entering the stage, as written in a stage file
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnEvent {
+    /// Seconds into the stage the first enemy appears
+    pub time: f32,
+    /// Name of the prefab to spawn
+    pub enemy: String,
+    pub position: Vec2,
+    #[serde(default = "crate::assets::one")]
+    pub count: u32,
+    /// Offset between the spawn positions of consecutive enemies
+    #[serde(default)]
+    pub spacing: Vec2,
+    /// Seconds between consecutive enemies
+    #[serde(default)]
+    pub interval: f32,
+    /// Path relative to the spawn position, followed before the enemy starts
+    /// its regular movement
+    #[serde(default)]
+    pub path: Option<PathConfig>,
+    /// Name of a behavior that takes over movement and attacks
+    #[serde(default)]
+    pub behavior: Option<String>,
+    /// Groups the enemies into a formation that moves as one, all members
+    /// spawn at once and `interval` is ignored
+    #[serde(default)]
+    pub formation: Option<FormationConfig>,
+    /// Name of a boss fight, its phases take over from `behavior`
+    #[serde(default)]
+    pub boss: Option<String>,
+    pub attack: AttackConfig,
+    #[serde(default)]
+    pub aim: Aim,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Stage {
+    pub events: Vec<SpawnEvent>,
+}
+
+/// A single enemy spawn, with the group of the event expanded
+pub struct ScheduledSpawn {
+    pub time: f32,
+    pub position: Vec2,
+    /// Index of the event in the stage
+    pub group: usize,
+    /// Index of the enemy within its event
+    pub member: u32,
+    pub event: SpawnEvent,
+}
+
+impl Stage {
+    pub fn load(path: &str) -> Result<Stage, String> {
+        let stage: Stage = crate::assets::load_ron(path)?;
+
+        for event in stage.events.iter() {
+            if !event.time.is_finite() || !event.interval.is_finite() {
+                return Err(format!(
+                    "{}: spawn of {} has a time or interval that is not finite",
+                    path, event.enemy
+                ));
+            }
+        }
+
+        Ok(stage)
+    }
+
+    /// Checks that every enemy, behavior and boss the spawns name exists
+    pub fn validate(
+        &self,
+        prefabs: &Prefabs,
+        behaviors: &Behaviors,
+        bosses: &Bosses,
+    ) -> Result<(), String> {
+        for event in self.events.iter() {
+            if !prefabs.contains(&event.enemy) {
+                return Err(format!(
+                    "spawn at {}s: unknown enemy {}",
+                    event.time, event.enemy
+                ));
+            }
+            if let Some(behavior) = &event.behavior {
+                if !behaviors.contains(behavior) {
+                    return Err(format!(
+                        "spawn of {} at {}s: unknown behavior {}",
+                        event.enemy, event.time, behavior
+                    ));
+                }
+            }
+            if let Some(boss) = &event.boss {
+                if !bosses.contains(boss) {
+                    return Err(format!(
+                        "spawn of {} at {}s: unknown boss {}",
+                        event.enemy, event.time, boss
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every enemy spawn of the stage, ordered by time
+    pub fn timeline(&self) -> Vec<ScheduledSpawn> {
+        let mut timeline = Vec::new();
+        for (group, event) in self.events.iter().enumerate() {
+            // Formations enter together so the group is complete from the start
+            let interval = if event.formation.is_some() {
+                0.0
+            } else {
+                event.interval
+            };
+            for i in 0..event.count {
+                timeline.push(ScheduledSpawn {
+                    time: event.time + interval * i as f32,
+                    position: event.position + event.spacing * i as f32,
+                    group,
+                    member: i,
+                    event: event.clone(),
+                });
+            }
+        }
+        timeline.sort_by(|a, b| a.time.total_cmp(&b.time));
+        timeline
+    }
+}
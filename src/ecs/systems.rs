@@ -1,17 +1,42 @@
 use sdl2::keyboard::Scancode;
 use specs::prelude::*;
+use specs::shrev::EventChannel;
 
 use crate::ecs::components::*;
+use crate::ecs::enemy::*;
+use crate::ecs::player::*;
+use crate::ecs::pool::*;
+use crate::ecs::prefab::Prefabs;
 use crate::ecs::resources::*;
 use crate::ecs::weapon::*;
 
 pub struct HealthSystem;
 
 impl<'a> System<'a> for HealthSystem {
-    type SystemData = (Entities<'a>, WriteStorage<'a, Health>, Read<'a, LazyUpdate>);
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Health>,
+        ReadStorage<'a, Enemy>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Pooled>,
+        Write<'a, ProjectilePool>,
+        Write<'a, EventChannel<GameEvent>>,
+        Read<'a, Prefabs>,
+        Read<'a, LazyUpdate>,
+    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut health_storage, world) = data;
+        let (
+            entities,
+            mut health_storage,
+            enemy_storage,
+            player_storage,
+            pooled_storage,
+            mut pool,
+            mut events,
+            prefabs,
+            world,
+        ) = data;
 
         for (entity, health) in (&entities, &mut health_storage).join() {
             for damage_event in health.damage_events.iter() {
@@ -19,9 +44,15 @@ impl<'a> System<'a> for HealthSystem {
 
                 if amount >= &health.health {
                     health.health = 0;
-                    (health.on_death)(*pos, &entities, &world);
-                    entities.delete(entity).expect("error deleeting entity")
-                // enemy died!
+                    (health.on_death)(*pos, &prefabs, &entities, &world);
+                    if let Some(enemy) = enemy_storage.get(entity) {
+                        events.single_write(GameEvent::EnemyKilled(*pos, enemy.points));
+                    } else if player_storage.contains(entity) {
+                        events.single_write(GameEvent::PlayerDied(*pos));
+                    }
+                    despawn(entity, &entities, &pooled_storage, &mut pool);
+                    // enemy died! later hits this frame should not kill it again
+                    break;
                 } else {
                     health.health -= amount;
                 }
@@ -37,19 +68,43 @@ impl<'a> System<'a> for LifetimeKiller {
     type SystemData = (
         Entities<'a>,
         WriteStorage<'a, Lifetime>,
+        ReadStorage<'a, Pooled>,
+        Write<'a, ProjectilePool>,
         Read<'a, DeltaTime>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut lifetime_storage, delta_time) = data;
+        let (entities, mut lifetime_storage, pooled_storage, mut pool, delta_time) = data;
         let delta_time = delta_time.0;
 
         for (entity, mut lifetime) in (&entities, &mut lifetime_storage).join() {
             lifetime.time_left -= delta_time;
             if lifetime.time_left < 0.0 {
-                entities
-                    .delete(entity)
-                    .expect("error deleting after lifetime ended")
+                despawn(entity, &entities, &pooled_storage, &mut pool);
+            }
+        }
+    }
+}
+
+pub struct OffscreenCullSystem;
+
+impl<'a> System<'a> for OffscreenCullSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, PlayfieldBounds>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, NoCull>,
+        ReadStorage<'a, Pooled>,
+        Write<'a, ProjectilePool>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, bounds, position_storage, no_cull_storage, pooled_storage, mut pool) = data;
+
+        for (entity, position, _) in (&entities, &position_storage, !&no_cull_storage).join() {
+            if !bounds.contains(position.position) {
+                despawn(entity, &entities, &pooled_storage, &mut pool);
             }
         }
     }
@@ -120,15 +175,42 @@ impl<'a> System<'a> for InputSystem {
             }
         }
 
-        let should_fire = input.get_key(Scancode::Space).held;
-
-        if should_fire {
-            for (weapon, _) in (&mut weapon_storage, &controlled).join() {
-                weapon.command = WeaponFireCommand::FireOnce;
-            }
-        } else {
-            for (weapon, _) in (&mut weapon_storage, &controlled).join() {
+        let cycle_mode = input.get_key(Scancode::Q).pressed;
+        let trigger = input.get_key(Scancode::Space);
+
+        for (weapon, _) in (&mut weapon_storage, &controlled).join() {
+            if cycle_mode {
+                let current = PLAYER_FIRE_MODES
+                    .iter()
+                    .position(|mode| {
+                        std::mem::discriminant(mode) == std::mem::discriminant(&weapon.mode)
+                    })
+                    .unwrap_or(0);
+                weapon.mode = PLAYER_FIRE_MODES[(current + 1) % PLAYER_FIRE_MODES.len()];
                 weapon.command = WeaponFireCommand::Waiting;
+                weapon.charge = 0.0;
+            }
+
+            match weapon.mode {
+                FireMode::Auto => {
+                    if trigger.held {
+                        weapon.command = WeaponFireCommand::AutoFire;
+                    } else {
+                        weapon.command = WeaponFireCommand::Waiting;
+                    }
+                }
+                FireMode::Burst { .. } => {
+                    if trigger.pressed {
+                        weapon.command = WeaponFireCommand::Burst(1);
+                    }
+                }
+                FireMode::Charge { .. } => {
+                    if trigger.held {
+                        weapon.command = WeaponFireCommand::Charge;
+                    } else if trigger.released {
+                        weapon.command = WeaponFireCommand::ReleaseCharge;
+                    }
+                }
             }
         }
     }
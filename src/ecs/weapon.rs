@@ -1,20 +1,168 @@
+use crate::ecs::bullet::*;
 use crate::ecs::collision::*;
 use crate::ecs::components::*;
+use crate::ecs::pool::*;
+use crate::ecs::prefab::*;
+use crate::ecs::rank::*;
 use crate::ecs::resources::*;
+use crate::vec2::Vec2;
+use serde::Deserialize;
 use specs::prelude::*;
 
+pub const MAX_POWER: u32 = 128;
+pub const POWER_LOST_ON_DEATH: u32 = 32;
+pub const PLAYER_BULLET_PREFAB: &str = "player_bullet";
+pub const ENEMY_BULLET_PREFAB: &str = "enemy_bullet";
+
 pub enum WeaponFireCommand {
     Waiting,
+    #[allow(dead_code)]
     FireOnce,
     FireAmount(u32),
+    /// Keeps firing whenever the cooldown allows
+    AutoFire,
+    /// Fires the given number of bursts as configured by `FireMode::Burst`
+    Burst(u32),
+    /// Builds up charge while held
+    Charge,
+    /// Fires a shot scaled by the charge built up so far
+    ReleaseCharge,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum FireMode {
+    /// Fires for as long as the trigger is held
+    Auto,
+    /// Fires `shots` shots per trigger pull, then waits `delay` seconds
+    Burst { shots: u32, delay: f32 },
+    /// Charges while held, a full charge of `max_charge` seconds makes the
+    /// released shot `max_multiplier` times as big and strong
+    Charge {
+        max_charge: f32,
+        max_multiplier: f32,
+    },
+}
+
+/// Fire modes the player can cycle through
+pub const PLAYER_FIRE_MODES: [FireMode; 3] = [
+    FireMode::Auto,
+    FireMode::Burst {
+        shots: 5,
+        delay: 0.2,
+    },
+    FireMode::Charge {
+        max_charge: 1.5,
+        max_multiplier: 4.0,
+    },
+];
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum FirePattern {
+    Single,
+    /// Fires `count` bullets fanned out evenly over `spread` radians
+    Spread(u32, f32),
+}
+
+impl FirePattern {
+    /// The same pattern with `extra` more bullets over the same spread
+    fn densified(&self, extra: u32) -> FirePattern {
+        match *self {
+            FirePattern::Single => FirePattern::Single,
+            FirePattern::Spread(count, spread) => FirePattern::Spread(count + extra, spread),
+        }
+    }
+
+    /// Rotation in radians of each bullet relative to the firing direction
+    fn angles(&self) -> Vec<f32> {
+        match *self {
+            FirePattern::Single => vec![0.0],
+            FirePattern::Spread(count, spread) => {
+                if count <= 1 {
+                    return vec![0.0];
+                }
+                let step = spread / (count - 1) as f32;
+                (0..count)
+                    .map(|i| -spread / 2.0 + step * i as f32)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Where a weapon points its shots
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+pub enum Aim {
+    /// Straight along the weapon's speed, up for positive speeds
+    #[default]
+    Fixed,
+    /// At where the target is right now
+    Direct,
+    /// At where the target will be when the bullet gets there, assuming it
+    /// keeps moving the way it does
+    Lead,
+}
+
+/// What an aiming weapon shoots at
+#[derive(Copy, Clone, Debug)]
+pub struct Target {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+/// Weapon stats used once the power level reaches `min_power`
+pub struct PowerTier {
+    pub min_power: u32,
+    pub pattern: FirePattern,
+    pub time_between_shots: f32,
+    pub damage: u32,
 }
 
+pub const PLAYER_POWER_TIERS: [PowerTier; 4] = [
+    PowerTier {
+        min_power: 0,
+        pattern: FirePattern::Single,
+        time_between_shots: 0.015,
+        damage: 25,
+    },
+    PowerTier {
+        min_power: 16,
+        pattern: FirePattern::Spread(3, 0.2),
+        time_between_shots: 0.015,
+        damage: 25,
+    },
+    PowerTier {
+        min_power: 48,
+        pattern: FirePattern::Spread(5, 0.35),
+        time_between_shots: 0.0125,
+        damage: 30,
+    },
+    PowerTier {
+        min_power: MAX_POWER,
+        pattern: FirePattern::Spread(7, 0.5),
+        time_between_shots: 0.01,
+        damage: 35,
+    },
+];
+
 pub struct Weapon {
     pub speed: f32,
     pub time_between_shots: f32,
     pub cooldown: f32,
     pub command: WeaponFireCommand,
     pub damage: u32,
+    pub pattern: FirePattern,
+    pub power: u32,
+    /// Name of the `BulletType` this weapon fires
+    pub bullet_type: String,
+    pub mode: FireMode,
+    /// Seconds of charge built up for a charge shot
+    pub charge: f32,
+    pub aim: Aim,
+    /// Set every frame for aiming weapons, `None` falls back to firing straight
+    pub target: Option<Target>,
+    /// Whether the `Rank` makes this weapon fire faster and denser
+    pub scales_with_rank: bool,
+    burst_shots_fired: u32,
 }
 
 impl Weapon {
@@ -25,6 +173,70 @@ impl Weapon {
             cooldown: 0.0,
             command: WeaponFireCommand::Waiting,
             damage,
+            pattern: FirePattern::Single,
+            power: 0,
+            bullet_type: PLAYER_BULLET.to_string(),
+            mode: FireMode::Auto,
+            charge: 0.0,
+            aim: Aim::Fixed,
+            target: None,
+            scales_with_rank: false,
+            burst_shots_fired: 0,
+        }
+    }
+
+    pub fn with_bullet_type(mut self, bullet_type: &str) -> Weapon {
+        self.bullet_type = bullet_type.to_string();
+        self
+    }
+
+    pub fn with_aim(mut self, aim: Aim) -> Weapon {
+        self.aim = aim;
+        self
+    }
+
+    pub fn with_rank_scaling(mut self) -> Weapon {
+        self.scales_with_rank = true;
+        self
+    }
+
+    /// Damage and size multiplier for a shot released with the current charge
+    fn charge_multiplier(&self) -> f32 {
+        match self.mode {
+            FireMode::Charge {
+                max_charge,
+                max_multiplier,
+            } => 1.0 + (max_multiplier - 1.0) * (self.charge / max_charge).min(1.0),
+            _ => 1.0,
+        }
+    }
+
+    /// Velocity of an unrotated shot fired from `origin`
+    fn shot_velocity(&self, origin: Vec2) -> Vec2 {
+        let fixed = Vec2::up() * self.speed;
+        let target = match (self.aim, self.target) {
+            (Aim::Fixed, _) | (_, None) => return fixed,
+            (Aim::Direct, Some(target)) => target.position,
+            (Aim::Lead, Some(target)) => {
+                intercept_point(origin, self.speed.abs(), target).unwrap_or(target.position)
+            }
+        };
+
+        let offset = target - origin;
+        if offset.squared_length() <= f32::EPSILON {
+            return fixed;
+        }
+        offset.normalzed() * self.speed.abs()
+    }
+
+    /// Sets the power level and takes the stats of the highest tier it reaches
+    pub fn apply_power(&mut self, power: u32, tiers: &[PowerTier]) {
+        self.power = power.min(MAX_POWER);
+
+        if let Some(tier) = tiers.iter().rev().find(|t| self.power >= t.min_power) {
+            self.pattern = tier.pattern;
+            self.time_between_shots = tier.time_between_shots;
+            self.damage = tier.damage;
         }
     }
 }
@@ -43,10 +255,16 @@ impl<'a> System<'a> for WeaponSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, CircleCollider>,
         WriteStorage<'a, Weapon>,
-        Read<'a, LazyUpdate>,
+        Read<'a, BulletTypes>,
+        Read<'a, Rank>,
+        Write<'a, ProjectilePool>,
+        Read<'a, Prefabs>,
     );
 
-    fn run(&mut self, (entities, delta, position, collider, mut weapon, world): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, delta, position, collider, mut weapon, bullet_types, rank, mut pool, prefabs): Self::SystemData,
+    ) {
         let delta = delta.0;
 
         let handle_weapon = |(_entity, position, collider, weapon): (
@@ -57,14 +275,36 @@ impl<'a> System<'a> for WeaponSystem {
         )| {
             weapon.cooldown -= delta;
 
+            if let WeaponFireCommand::Charge = weapon.command {
+                weapon.charge += delta;
+            }
+
             if weapon.cooldown <= 0.0 {
                 match weapon.command {
                     WeaponFireCommand::FireOnce => {
-                        spawn_bullet(position, collider, weapon, &entities, &world);
+                        fire_pattern(
+                            &prefabs,
+                            position,
+                            collider,
+                            weapon,
+                            &bullet_types,
+                            &rank,
+                            &mut pool,
+                            1.0,
+                        );
                         weapon.command = WeaponFireCommand::Waiting;
                     }
                     WeaponFireCommand::FireAmount(amount) => {
-                        spawn_bullet(position, collider, weapon, &entities, &world);
+                        fire_pattern(
+                            &prefabs,
+                            position,
+                            collider,
+                            weapon,
+                            &bullet_types,
+                            &rank,
+                            &mut pool,
+                            1.0,
+                        );
                         let new_amount = amount - 1;
                         if new_amount > 0 {
                             weapon.command = WeaponFireCommand::FireAmount(new_amount);
@@ -72,6 +312,60 @@ impl<'a> System<'a> for WeaponSystem {
                             weapon.command = WeaponFireCommand::Waiting;
                         }
                     }
+                    WeaponFireCommand::AutoFire => {
+                        fire_pattern(
+                            &prefabs,
+                            position,
+                            collider,
+                            weapon,
+                            &bullet_types,
+                            &rank,
+                            &mut pool,
+                            1.0,
+                        );
+                    }
+                    WeaponFireCommand::Burst(bursts) => {
+                        fire_pattern(
+                            &prefabs,
+                            position,
+                            collider,
+                            weapon,
+                            &bullet_types,
+                            &rank,
+                            &mut pool,
+                            1.0,
+                        );
+                        weapon.burst_shots_fired += 1;
+
+                        let (shots, delay) = match weapon.mode {
+                            FireMode::Burst { shots, delay } => (shots, delay),
+                            _ => (1, weapon.time_between_shots),
+                        };
+                        if weapon.burst_shots_fired >= shots {
+                            weapon.burst_shots_fired = 0;
+                            weapon.cooldown = delay;
+                            if bursts > 1 {
+                                weapon.command = WeaponFireCommand::Burst(bursts - 1);
+                            } else {
+                                weapon.command = WeaponFireCommand::Waiting;
+                            }
+                        }
+                    }
+                    WeaponFireCommand::ReleaseCharge => {
+                        let multiplier = weapon.charge_multiplier();
+                        fire_pattern(
+                            &prefabs,
+                            position,
+                            collider,
+                            weapon,
+                            &bullet_types,
+                            &rank,
+                            &mut pool,
+                            multiplier,
+                        );
+                        weapon.charge = 0.0;
+                        weapon.command = WeaponFireCommand::Waiting;
+                    }
                     _ => {}
                 }
             }
@@ -83,66 +377,191 @@ impl<'a> System<'a> for WeaponSystem {
     }
 }
 
-fn spawn_bullet(
+#[allow(clippy::too_many_arguments)]
+fn fire_pattern(
+    prefabs: &Prefabs,
     position: &Position,
     collider: &CircleCollider,
     weapon: &mut Weapon,
-    entities: &specs::Entities,
-    world: &specs::LazyUpdate,
+    bullet_types: &BulletTypes,
+    rank: &Rank,
+    pool: &mut ProjectilePool,
+    scale: f32,
 ) {
-    let projectile = entities.create();
-    weapon.cooldown = weapon.time_between_shots;
-
-    world.insert(projectile, Projectile {});
-    world.insert(projectile, *position);
-    world.insert(
-        projectile,
-        Sprite {
-            spritesheet: crate::assets::BULLET_SPRITE_ID,
-            size: sdl2::rect::Point::new(crate::assets::BULLET_SIZE, crate::assets::BULLET_SIZE),
-            src_rect: sdl2::rect::Rect::new(0, 0, 16, 16),
-        },
-    );
-    world.insert(projectile, Velocity::new(0.0, -weapon.speed as f32));
-    world.insert(projectile, Lifetime { time_left: 1.0 });
-    world.insert(
-        projectile,
-        CircleCollider {
-            radius: 16.0,
-            layer: collider.layer,
-            collides_with: collider.collides_with,
-        },
-    );
-    world.insert(projectile, Health::new(1, on_bullet_dead));
-    world.insert(
-        projectile,
-        Damage {
-            damage: weapon.damage,
-        },
-    );
+    let (pattern, speed, fire_rate) = if weapon.scales_with_rank {
+        (
+            weapon.pattern.densified(rank.extra_bullets()),
+            rank.bullet_speed(),
+            rank.fire_rate(),
+        )
+    } else {
+        (weapon.pattern, 1.0, 1.0)
+    };
+    weapon.cooldown = weapon.time_between_shots / fire_rate;
+
+    let bullet_type = bullet_types.get(&weapon.bullet_type);
+    let direction = weapon.shot_velocity(position.position) * speed;
+    for angle in pattern.angles() {
+        let velocity = direction.rotated(angle);
+        pool.request(bullet_spawn(
+            prefabs,
+            position,
+            collider,
+            weapon,
+            bullet_type,
+            velocity,
+            scale,
+        ));
+    }
+}
+
+/// Where a bullet fired from `origin` at `speed` meets a target moving in a
+/// straight line, `None` if the bullet can never catch up
+fn intercept_point(origin: Vec2, speed: f32, target: Target) -> Option<Vec2> {
+    let offset = target.position - origin;
+    // Solves |offset + velocity * t| = speed * t for the earliest t > 0
+    let a = target.velocity.squared_length() - speed * speed;
+    let b = 2.0 * offset.dot(&target.velocity);
+    let c = offset.squared_length();
+
+    let time = if a.abs() <= f32::EPSILON {
+        if b.abs() <= f32::EPSILON {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (t1, t2) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+        match (t1 > 0.0, t2 > 0.0) {
+            (true, true) => t1.min(t2),
+            (true, false) => t1,
+            (false, true) => t2,
+            (false, false) => return None,
+        }
+    };
+
+    if time <= 0.0 {
+        return None;
+    }
+    Some(target.position + target.velocity * time)
+}
+
+/// A bullet from the `player_bullet` or `enemy_bullet` prefab, whichever
+/// side the shooter's `collider` is on, with the looks, speed and damage of
+/// this shot
+pub fn bullet_spawn(
+    prefabs: &Prefabs,
+    position: &Position,
+    collider: &CircleCollider,
+    weapon: &Weapon,
+    bullet_type: &BulletType,
+    velocity: Vec2,
+    scale: f32,
+) -> ProjectileSpawn {
+    let prefab = if collider.layer.any(&LayerMask::from_enum(Layers::Player)) {
+        PLAYER_BULLET_PREFAB
+    } else {
+        ENEMY_BULLET_PREFAB
+    };
+    let size = (bullet_type.size as f32 * scale) as i32;
+    prefabs.projectile_with(prefab, position.position, |component| match component {
+        ComponentData::Sprite {
+            spritesheet,
+            src_rect,
+            size: sprite_size,
+            align_to_velocity,
+            blend,
+            ..
+        } => {
+            let rect = bullet_type.src_rect;
+            *spritesheet = bullet_type.spritesheet;
+            *src_rect = (rect.x(), rect.y(), rect.width(), rect.height());
+            *sprite_size = (size, size);
+            *align_to_velocity = bullet_type.align_to_velocity;
+            *blend = bullet_type.blend;
+        }
+        ComponentData::Velocity(bullet_velocity) => *bullet_velocity = velocity,
+        ComponentData::Collider { radius, .. } => *radius = bullet_type.collider_radius * scale,
+        ComponentData::Damage(damage) => {
+            *damage = ((weapon.damage + bullet_type.damage) as f32 * scale) as u32
+        }
+        _ => {}
+    })
 }
 
-fn on_bullet_dead(
+pub fn on_bullet_dead(
     position: crate::vec2::Vec2,
+    prefabs: &crate::ecs::prefab::Prefabs,
     entities: &specs::Entities,
     world: &specs::LazyUpdate,
 ) {
-    let explosion = entities.create();
-
-    world.insert(
-        explosion,
-        Position {
-            position: crate::vec2::Vec2::randomize(position, 10.0),
-        },
+    prefabs.spawn(
+        "small_explosion",
+        crate::vec2::Vec2::randomize(position, 10.0),
+        entities,
+        world,
     );
-    world.insert(
-        explosion,
-        Sprite {
-            spritesheet: crate::assets::EXPLOSION_SPRITE_ID,
-            size: sdl2::rect::Point::new(32, 32),
-            src_rect: sdl2::rect::Rect::new(0, 0, 64, 64),
-        },
-    );
-    world.insert(explosion, crate::ecs::animation::Animation::new(30, 4, 4));
-    world.insert(explosion, Lifetime { time_left: 0.50 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    #[test]
+    fn intercept_of_a_still_target_is_the_target() {
+        let target = Target {
+            position: v(100.0, 0.0),
+            velocity: Vec2::default(),
+        };
+        let point = intercept_point(Vec2::default(), 50.0, target).unwrap();
+        assert!(Vec2::distance(point, v(100.0, 0.0)) < 1e-3);
+    }
+
+    #[test]
+    fn bullet_and_target_reach_the_intercept_together() {
+        let origin = v(0.0, 0.0);
+        let speed = 500.0;
+        let target = Target {
+            position: v(300.0, -400.0),
+            velocity: v(-120.0, 60.0),
+        };
+        let point = intercept_point(origin, speed, target).unwrap();
+
+        let bullet_time = Vec2::distance(origin, point) / speed;
+        let target_time = Vec2::distance(target.position, point) / target.velocity.length();
+        assert!((bullet_time - target_time).abs() < 1e-3);
+    }
+
+    #[test]
+    fn no_intercept_when_the_target_outruns_the_bullet() {
+        let target = Target {
+            position: v(100.0, 0.0),
+            velocity: v(200.0, 0.0),
+        };
+        assert!(intercept_point(Vec2::default(), 100.0, target).is_none());
+    }
+
+    #[test]
+    fn intercept_at_equal_speeds_needs_the_target_to_come_closer() {
+        let approaching = Target {
+            position: v(100.0, 0.0),
+            velocity: v(-100.0, 0.0),
+        };
+        let point = intercept_point(Vec2::default(), 100.0, approaching).unwrap();
+        assert!(Vec2::distance(point, v(50.0, 0.0)) < 1e-3);
+
+        let leaving = Target {
+            position: v(100.0, 0.0),
+            velocity: v(100.0, 0.0),
+        };
+        assert!(intercept_point(Vec2::default(), 100.0, leaving).is_none());
+    }
 }
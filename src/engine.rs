@@ -1,15 +1,34 @@
 use sdl2::event::Event;
-use sdl2::image::{self, InitFlag, LoadTexture};
+use sdl2::image::{self, InitFlag};
 use sdl2::pixels::Color;
 use specs::prelude::*;
 
+use crate::atlas::Atlas;
 use crate::ecs::animation::*;
+use crate::ecs::background::*;
+use crate::ecs::behavior::*;
+use crate::ecs::boss::*;
+use crate::ecs::bullet::BulletTypes;
+use crate::ecs::camera::*;
 use crate::ecs::collision::*;
 use crate::ecs::components::*;
+use crate::ecs::debug::*;
+use crate::ecs::difficulty::Difficulty;
 use crate::ecs::enemy::*;
+use crate::ecs::font::BitmapFont;
+use crate::ecs::formation::*;
+use crate::ecs::hud::*;
+use crate::ecs::particle::*;
+use crate::ecs::path::*;
+use crate::ecs::pickup::*;
 use crate::ecs::player::*;
+use crate::ecs::pool::*;
+use crate::ecs::prefab::Prefabs;
+use crate::ecs::rank::*;
 use crate::ecs::renderer;
 use crate::ecs::resources::*;
+use crate::ecs::score::*;
+use crate::ecs::stage::Stage;
 use crate::ecs::systems::*;
 use crate::ecs::weapon::*;
 
@@ -18,13 +37,19 @@ use crate::input;
 pub struct Engine {
     window_width: u32,
     window_height: u32,
+    /// Preset to play on
+    difficulty: Difficulty,
+    /// Lets F3 turn on the debug overlay in release builds
+    debug_overlay: bool,
 }
 
 impl Engine {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, difficulty: Difficulty, debug_overlay: bool) -> Self {
         Engine {
             window_width: width,
             window_height: height,
+            difficulty,
+            debug_overlay,
         }
     }
 
@@ -45,20 +70,8 @@ impl Engine {
         let mut canvas = window.into_canvas().build().expect("could not make canvas");
         let texture_creator = canvas.texture_creator();
 
-        let textures = [
-            texture_creator
-                .load_texture(crate::assets::PLAYER_SPRITE_PATH)
-                .expect("could not load texture"),
-            texture_creator
-                .load_texture(crate::assets::BOSS_SPRITE_PATH)
-                .expect("could not load texture"),
-            texture_creator
-                .load_texture(crate::assets::BULLET_SPRITE_PATH)
-                .expect("could not load texture"),
-            texture_creator
-                .load_texture(crate::assets::EXPLOSION_SPRITE_PATH)
-                .expect("could not load texture"),
-        ];
+        let mut atlas = Atlas::build(&crate::assets::SPRITE_PATHS, &texture_creator)
+            .expect("could not build texture atlas");
 
         canvas.set_draw_color(Color::RGB(0, 255, 255));
         canvas.clear();
@@ -66,6 +79,22 @@ impl Engine {
 
         let mut input = input::Input::new();
 
+        let stage = Stage::load(crate::assets::STAGE_PATH).expect("could not load stage");
+        let behaviors =
+            Behaviors::load(crate::assets::BEHAVIORS_PATH).expect("could not load behaviors");
+        let bosses = Bosses::load(crate::assets::BOSSES_PATH).expect("could not load bosses");
+        let prefabs = Prefabs::load(crate::assets::PREFABS_PATH).expect("could not load prefabs");
+        let bullet_types = BulletTypes::default();
+        // Data files name each other's entries, typos are caught here instead
+        // of when the entry is first used
+        bosses
+            .validate(&behaviors, &bullet_types)
+            .expect("invalid bosses");
+        prefabs.validate(&bullet_types).expect("invalid prefabs");
+        stage
+            .validate(&prefabs, &behaviors, &bosses)
+            .expect("invalid stage");
+
         // Register systems
         let mut dispatcher = DispatcherBuilder::new()
             .with(InputSystem, "input", &[])
@@ -73,48 +102,101 @@ impl Engine {
             .with(CollisionSystem::new(), "collision", &[])
             .with(WeaponSystem, "weapon system", &[])
             .with(LifetimeKiller, "lifetime", &[])
+            .with(OffscreenCullSystem, "offscreen cull", &[])
             .with(HealthSystem, "health", &[])
+            .with(
+                ProjectilePoolSystem,
+                "projectile pool",
+                &["weapon system", "lifetime", "health", "offscreen cull"],
+            )
             .with(AnimationSystem, "animation", &[])
+            .with(FadeSystem, "fade", &["lifetime"])
+            .with(ParticleSystem, "particles", &["position updater"])
+            .with(BackgroundSystem, "background", &[])
             .with(EnemySystem, "enemy", &[])
-            .with(EnemySpawnerSystem::default(), "enemy spawner", &[])
-            .with(PlayerRespawnSystem, "player spawner", &[])
+            .with(PathSystem, "path", &[])
+            .with(BehaviorSystem, "behavior", &[])
+            .with(BossSystem, "boss", &[])
+            .with(FormationSystem, "formation", &[])
+            .with(EnemySpawnerSystem::new(&stage), "enemy spawner", &[])
+            .with(PickupSystem, "pickup", &[])
+            .with(RankSystem::default(), "rank", &["health", "enemy spawner"])
+            .with(GrazeSystem::default(), "graze", &["position updater"])
+            .with(
+                ScoreSystem::default(),
+                "score",
+                &["health", "graze", "boss", "pickup"],
+            )
+            .with(PlayerRespawnSystem::default(), "player spawner", &[])
+            .with(CameraSystem::default(), "camera", &["health"])
+            .with(
+                HudSystem::default(),
+                "hud",
+                &["score", "rank", "player spawner"],
+            )
+            .with(DebugOverlaySystem, "debug overlay", &[])
             .build();
 
         // Register required components
         let mut world = World::new();
+        world.insert(self.difficulty.clone());
+        let playfield =
+            PlayfieldBounds::new(self.window_width as f32, self.window_height as f32, 64.0);
+        world.insert(
+            Camera::new(self.window_width as f32, self.window_height as f32)
+                .with_bounds(playfield.min, playfield.max),
+        );
+        world.insert(playfield);
+        world.insert(behaviors);
+        world.insert(bosses);
+        world.insert(prefabs);
+        world.insert(bullet_types);
+        world.insert(
+            Background::load(crate::assets::BACKGROUND_PATH).expect("could not load background"),
+        );
+        world.insert(BitmapFont::load(crate::assets::FONT_PATH).expect("could not load font"));
+        world.insert(Hud::load(crate::assets::HUD_PATH).expect("could not load HUD"));
+        world.insert(DebugOverlay::new(self.debug_overlay));
         world.register::<Weapon>();
         world.register::<Lifetime>();
         world.register::<Enemy>();
         world.register::<Player>();
         world.register::<Projectile>();
         world.register::<Damage>();
+        world.register::<Pickup>();
         dispatcher.setup(&mut world);
         renderer::SystemData::setup(&mut world);
 
         let mut last_frame_time = std::time::Instant::now();
         let mut event_pump = sdl_context.event_pump().unwrap();
         'running: loop {
-            let start_time = std::time::Instant::now();
-
             canvas.set_draw_color(Color::RGB(0, 0, 0));
             canvas.clear();
 
+            input.clear_transitions();
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. } => {
                         break 'running;
                     }
 
-                    Event::KeyDown { scancode, .. } => {
-                        if let Some(scancode) = scancode {
-                            input.key_state[scancode as usize].held = true;
-                        }
+                    Event::KeyDown {
+                        scancode: Some(scancode),
+                        repeat: false,
+                        ..
+                    } => {
+                        let key = &mut input.key_state[scancode as usize];
+                        key.pressed = true;
+                        key.held = true;
                     }
 
-                    Event::KeyUp { scancode, .. } => {
-                        if let Some(scancode) = scancode {
-                            input.key_state[scancode as usize].held = false;
-                        }
+                    Event::KeyUp {
+                        scancode: Some(scancode),
+                        ..
+                    } => {
+                        let key = &mut input.key_state[scancode as usize];
+                        key.released = true;
+                        key.held = false;
                     }
 
                     Event::MouseMotion { x, y, .. } => {
@@ -154,17 +236,12 @@ impl Engine {
             renderer::render(
                 &mut canvas,
                 Color::RGB(0, 0, 0),
-                &textures,
+                &mut atlas,
                 world.system_data(),
             )
             .expect("Render failed");
 
             canvas.present();
-            let loop_duration = std::time::Instant::now() - start_time;
-
-            if input.get_key(sdl2::keyboard::Scancode::F12).held {
-                println!("FPS {}", 1.0 / loop_duration.as_secs_f64());
-            }
         } // Loop
     }
 }
@@ -2,10 +2,11 @@ use sdl2::keyboard::Scancode;
 use sdl2::mouse::MouseButton;
 
 const NUM_MOUSE_BUTTONS: usize = 8;
-#[allow(dead_code)]
 #[derive(Clone, Copy, Default)]
 pub struct ButtonState {
+    /// Went down this frame
     pub pressed: bool,
+    /// Went up this frame
     pub released: bool,
     pub held: bool,
 }
@@ -43,6 +44,14 @@ impl Input {
     }
     pub fn get_mouse_pos() {}
 
+    /// Forgets which keys were pressed or released last frame
+    pub fn clear_transitions(&mut self) {
+        for key in self.key_state.iter_mut() {
+            key.pressed = false;
+            key.released = false;
+        }
+    }
+
     pub fn get_mouse_button(&self, btn: MouseButton) -> MouseState {
         self.mouse_state[btn as usize]
     }
@@ -1,21 +1,63 @@
 mod assets;
+mod atlas;
+mod bench;
 mod engine;
 mod input;
 mod vec2;
 mod ecs {
     pub mod animation;
+    pub mod background;
+    pub mod behavior;
+    pub mod boss;
+    pub mod bullet;
+    pub mod camera;
     pub mod collision;
     pub mod components;
+    pub mod debug;
+    pub mod difficulty;
     pub mod enemy;
+    pub mod font;
+    pub mod formation;
+    pub mod hud;
+    pub mod particle;
+    pub mod path;
+    pub mod pickup;
     pub mod player;
+    pub mod pool;
+    pub mod prefab;
+    pub mod rank;
     pub mod renderer;
     pub mod resources;
+    pub mod score;
+    pub mod stage;
     pub mod systems;
     pub mod weapon;
 }
 
 pub fn main() {
-    let engine = engine::Engine::new(1600, 900);
+    if std::env::args().any(|arg| arg == "--bench-projectiles") {
+        bench::projectiles();
+        return;
+    }
+
+    // Pick a preset with --difficulty easy|normal|hard|lunatic
+    let difficulty = std::env::args()
+        .skip_while(|arg| arg != "--difficulty")
+        .nth(1)
+        .unwrap_or_else(|| assets::DEFAULT_DIFFICULTY.to_string());
+    let difficulty = match ecs::difficulty::Difficulty::load_preset(&difficulty) {
+        Ok(difficulty) => difficulty,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // F3 toggles the debug overlay, which release builds only allow with
+    // --debug-overlay
+    let debug_overlay = std::env::args().any(|arg| arg == "--debug-overlay");
+
+    let engine = engine::Engine::new(1600, 900, difficulty, debug_overlay);
 
     engine.run();
 }
@@ -1,6 +1,7 @@
 use rand::Rng;
+use serde::Deserialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -48,6 +49,15 @@ impl Vec2 {
         diff.normalzed()
     }
 
+    /// Rotates the vector by `angle` radians
+    pub fn rotated(&self, angle: f32) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2 {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+
     pub fn distance(from: Vec2, to: Vec2) -> f32 {
         let diff = to - from;
         diff.length()